@@ -1,5 +1,5 @@
 #[allow(unused_imports)]
-use bitmax_rs::{model, request, BitMaxClient};
+use bitmax_rs::{model, request, BitMaxClient, Credentials};
 use failure::Fallible;
 
 async fn req<Q: request::Request + std::fmt::Debug>(c: &BitMaxClient, req: Q) {
@@ -15,10 +15,7 @@ async fn req<Q: request::Request + std::fmt::Debug>(c: &BitMaxClient, req: Q) {
 async fn main() -> Fallible<()> {
     env_logger::init();
 
-    let private_key: String = std::env::var("BITMAX_PRIVATE").unwrap();
-    let public_key: String = std::env::var("BITMAX_PUBLIC").unwrap();
-
-    let c = BitMaxClient::with_auth(&public_key, &private_key, Some(6))?;
+    let c = Credentials::from_env()?.client().await?;
 
     req(&c, request::Products).await;
 
@@ -52,20 +49,13 @@ async fn main() -> Fallible<()> {
 
     //req(
     //    &c,
-    //    request::PlaceOrder {
-    //        account_type: model::AccountType::Cash,
-    //        id: None,
-    //        time: chrono::Utc::now().timestamp_millis(),
-    //        order_qty: "0.5".parse().unwrap(),
-    //        order_price: Some("200.00".parse().unwrap()),
-    //        side: model::OrderSide::Sell,
-    //        stop_price: None,
-    //        post_only: None,
-    //        time_in_force: model::TimeInForce::GTC,
-    //        resp_inst: request::ResponseInstruction::Accept,
-    //        symbol: "BNB/USDT",
-    //        order_type: model::OrderType::Limit,
-    //    },
+    //    request::PlaceOrder::limit(
+    //        "BNB/USDT",
+    //        model::OrderSide::Sell,
+    //        "0.5".parse().unwrap(),
+    //        "200.00".parse().unwrap(),
+    //    )
+    //    .with_resp_inst(request::ResponseInstruction::Accept),
     //)
     //.await;
 
@@ -85,27 +75,20 @@ async fn main() -> Fallible<()> {
     //        id: None,
     //        order_id: "a173c7d7067fU6638994975sbnbuknTq",
     //        symbol: "BNB/USDT",
-    //        time: chrono::Utc::now().timestamp_millis(),
+    //        time: model::Timestamp::now(),
     //    },
     //)
     //.await;
 
     //req(
     //    &c,
-    //    request::PlaceOrder {
-    //        account_type: model::AccountType::Cash,
-    //        id: None,
-    //        time: chrono::Utc::now().timestamp_millis(),
-    //        order_qty: "0.5".parse().unwrap(),
-    //        order_price: Some("200.00".parse().unwrap()),
-    //        side: model::OrderSide::Sell,
-    //        stop_price: None,
-    //        post_only: None,
-    //        time_in_force: model::TimeInForce::GTC,
-    //        resp_inst: request::ResponseInstruction::Accept,
-    //        symbol: "BNB/USDT",
-    //        order_type: model::OrderType::Limit,
-    //    },
+    //    request::PlaceOrder::limit(
+    //        "BNB/USDT",
+    //        model::OrderSide::Sell,
+    //        "0.5".parse().unwrap(),
+    //        "200.00".parse().unwrap(),
+    //    )
+    //    .with_resp_inst(request::ResponseInstruction::Accept),
     //)
     //.await;
 
@@ -1,17 +1,17 @@
+#[allow(unused_imports)]
 use std::time::{Duration, Instant};
 
-use bitmax_rs::{model, request, BitMaxClient};
+#[allow(unused_imports)]
+use bitmax_rs::{model, request, BitMaxClient, Credentials};
 use failure::Fallible;
+#[allow(unused_imports)]
 use futures::{SinkExt, StreamExt};
 
 #[tokio::main]
 async fn main() -> Fallible<()> {
     env_logger::init();
 
-    let private_key: String = std::env::var("BITMAX_PRIVATE").unwrap();
-    let public_key: String = std::env::var("BITMAX_PUBLIC").unwrap();
-
-    let c = BitMaxClient::with_auth(&public_key, &private_key, Some(6))?;
+    let c = Credentials::from_env()?.client().await?;
     let mut ws = c.websocket_all().await?;
 
     // ------ Subscription examples -------
@@ -60,25 +60,18 @@ async fn main() -> Fallible<()> {
     //})
     //.await?;
 
-    //ws.send(model::websocket::WsOutMessage::Request {
-    //    action: model::websocket::WsRequest::PlaceOrder(request::PlaceOrder {
-    //        account_type: model::AccountType::Cash,
-    //        id: None,
-    //        time: chrono::Utc::now().timestamp_millis(),
-    //        order_qty: "0.5".parse().unwrap(),
-    //        order_price: Some("200.00".parse().unwrap()),
-    //        side: model::OrderSide::Sell,
-    //        stop_price: None,
-    //        post_only: None,
-    //        time_in_force: model::TimeInForce::GTC,
-    //        resp_inst: request::ResponseInstruction::Accept,
-    //        symbol: "BNB/USDT",
-    //        order_type: model::OrderType::Limit,
-    //    }),
-    //    id: Some("abcdefg"),
-    //    account: Some(model::AccountType::Cash),
-    //})
-    //.await?;
+    //let mut order = model::websocket::WsRequest::PlaceOrder(
+    //    request::PlaceOrder::limit(
+    //        "BNB/USDT",
+    //        model::OrderSide::Sell,
+    //        "0.5".parse().unwrap(),
+    //        "200.00".parse().unwrap(),
+    //    )
+    //    .with_resp_inst(request::ResponseInstruction::Accept),
+    //);
+    //order.set_timestamp(c.server_time().into());
+    //ws.send(model::websocket::WsOutMessage::request(order, Some("abcdefg")))
+    //    .await?;
 
     // ------------------------------------
 
@@ -95,11 +88,6 @@ async fn main() -> Fallible<()> {
 
     // Iterate through message queue
     while let Some(msg) = ws.next().await {
-        // Send pong responses
-        if let Ok(model::websocket::WsInMessage::Ping { .. }) = msg {
-            ws.send(model::websocket::WsOutMessage::Pong).await?;
-        }
-
         // when 10 seconds elapse, unsub from the messages
         //if subbed && (Instant::now() - time) > dt {
         //    ws.send(model::websocket::WsOutMessage::Unsubscribe { id: None, ch })
@@ -1,86 +1,228 @@
-use chrono::Utc;
-use failure::Fallible;
-use hmac::{Hmac, Mac, NewMac};
-use log::debug;
-use reqwest::{Client, Method, Response};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use reqwest::{header::DATE, Client, Method, Response};
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::from_str;
-use sha2::Sha256;
 use url::Url;
 
+use crate::{
+    error::{ApiError, BitMaxError, Result},
+    model::Timestamp,
+};
+
+mod barhist;
+mod builder;
+mod clock;
+mod credentials;
+mod history;
+mod order_book;
+mod rate_limit;
+mod registry;
 pub mod request;
+mod retry;
+mod signer;
 mod util;
+mod wallet;
 pub mod websocket;
 
+pub use barhist::{BarSeries, BarhistRange};
+pub use builder::{BitMaxClientBuilder, Environment};
+use clock::ClockSync;
+pub use credentials::Credentials;
+pub use order_book::OrderBookFeed;
+pub use rate_limit::{Budget, EndpointClass, Quota, RateLimiter};
+pub use registry::{AssetBalance, InstrumentRegistry, Instruments};
 use request::Request;
+pub use retry::RetryPolicy;
+pub use signer::{HmacSigner, Signer};
 use util::{HeaderBuilder, ToUrlQuery};
 
-const HTTP_URL: &str = "bitmax.io";
 const API_URL: &str = "/api/pro/v1";
+const DEFAULT_USER_AGENT: &str = "bitmax-rs";
+/// The exchange pings every 15 seconds, the connection is considered dead after 3 missed pings
+const DEFAULT_WS_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(45);
 
 #[derive(Debug, Clone)]
 struct Auth {
     pub public_key: String,
-    pub private_key_bytes: Vec<u8>,
-    pub account_group: Option<u32>,
+    pub signer: Arc<dyn Signer>,
+    pub account_group: Arc<AccountGroup>,
+}
+
+/// Account group shared by the clones of a client, resolved on first use
+#[derive(Debug, Default)]
+struct AccountGroup {
+    value: RwLock<Option<u32>>,
+    // held while the group is being fetched, so that it is only fetched once
+    resolving: tokio::sync::Mutex<()>,
+}
+
+impl AccountGroup {
+    fn new(value: Option<u32>) -> Self {
+        Self {
+            value: RwLock::new(value),
+            resolving: Default::default(),
+        }
+    }
+
+    fn get(&self) -> Option<u32> {
+        *self.value.read().unwrap()
+    }
+
+    fn set(&self, value: u32) {
+        *self.value.write().unwrap() = Some(value);
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct BitMaxClient {
     client: Client,
     auth: Option<Auth>,
+    base_url: String,
+    ws_url: String,
+    user_agent: String,
+    ws_connect_timeout: Option<Duration>,
+    ws_heartbeat_timeout: Option<Duration>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    retry_policy: RetryPolicy,
+    clock: Arc<ClockSync>,
+}
+
+impl Default for BitMaxClient {
+    fn default() -> Self {
+        Self {
+            client: Default::default(),
+            auth: None,
+            base_url: Environment::Production.base_url().into(),
+            ws_url: Environment::Production.ws_url().into(),
+            user_agent: DEFAULT_USER_AGENT.into(),
+            ws_connect_timeout: None,
+            ws_heartbeat_timeout: Some(DEFAULT_WS_HEARTBEAT_TIMEOUT),
+            rate_limiter: None,
+            retry_policy: Default::default(),
+            clock: Default::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
 struct ResponseSchema<T> {
-    code: u32,
     data: T,
 }
 
+#[derive(Deserialize, Debug)]
+struct ResponseCode {
+    code: i64,
+}
+
 impl BitMaxClient {
     pub fn new() -> Self {
         Default::default()
     }
 
+    pub fn builder() -> BitMaxClientBuilder {
+        BitMaxClientBuilder::new()
+    }
+
     pub fn with_auth(
         public_key: &str,
         private_key: &str,
         account_group: Option<u32>,
-    ) -> Fallible<Self> {
-        Ok(Self {
-            auth: Some(Auth {
-                private_key_bytes: base64::decode(private_key)?,
-                public_key: public_key.into(),
-                account_group,
-            }),
-            client: Default::default(),
-        })
+    ) -> Result<Self> {
+        Self::builder()
+            .auth(public_key, private_key, account_group)
+            .build()
+    }
+
+    /// Client that delegates request signing to `signer`
+    pub fn with_signer<S: Signer + 'static>(
+        public_key: &str,
+        signer: S,
+        account_group: Option<u32>,
+    ) -> Result<Self> {
+        Self::builder()
+            .signer(public_key, signer, account_group)
+            .build()
     }
 
     /// Account group is needed for most of the account requests.
-    /// Make the `Account` request to get your account group.
-    pub fn set_account_group(&mut self, account_group: u32) -> Fallible<()> {
+    /// There is no need to set it by hand, it is fetched with the `AccountInfo`
+    /// request the first time it is needed. The group is shared by all clones of the client.
+    pub fn set_account_group(&self, account_group: u32) -> Result<()> {
         self.auth
-            .as_mut()
-            .ok_or_else(|| failure::format_err!("missing auth"))?
-            .account_group = Some(account_group);
+            .as_ref()
+            .ok_or(BitMaxError::MissingAuth)?
+            .account_group
+            .set(account_group);
 
         Ok(())
     }
 
-    fn attach_auth_headers<B: HeaderBuilder>(&self, builder: B, api_path: &str) -> Fallible<B> {
-        let auth = self
+    /// Returns the account group, fetching it from the exchange if it is not known yet
+    pub async fn account_group(&self) -> Result<u32> {
+        let group = &self
             .auth
             .as_ref()
-            .ok_or_else(|| failure::format_err!("missing auth keys"))?;
+            .ok_or(BitMaxError::MissingAuth)?
+            .account_group;
+        if let Some(value) = group.get() {
+            return Ok(value);
+        }
 
-        let timestamp = Utc::now().timestamp_millis();
+        let _resolving = group.resolving.lock().await;
+        if let Some(value) = group.get() {
+            return Ok(value);
+        }
+
+        debug!("account group is unknown, requesting account info");
+        let value = self
+            .send_with_retry(&mut request::AccountInfo)
+            .await?
+            .account_group as u32;
+        group.set(value);
+        Ok(value)
+    }
+
+    /// Rate limiter shared by all clones of this client, if one was configured
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_deref()
+    }
+
+    /// Estimated offset of the exchange clock from the local one (`server - local`),
+    /// `None` until the first response is received. It is applied to all signed timestamps.
+    pub fn time_offset(&self) -> Option<chrono::Duration> {
+        self.clock.offset()
+    }
+
+    /// Local time corrected with the estimated clock offset
+    pub fn server_time(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Refines the clock offset estimate with a lightweight public request
+    pub async fn sync_time(&self) -> Result<chrono::Duration> {
+        self.request(request::BarhistInfo).await?;
+        Ok(self.clock.offset().unwrap_or_else(chrono::Duration::zero))
+    }
+
+    /// `timestamp` must be the one sent in the request body, if any
+    async fn attach_auth_headers<B: HeaderBuilder>(
+        &self,
+        builder: B,
+        api_path: &str,
+        timestamp: Timestamp,
+    ) -> Result<B> {
+        let auth = self.auth.as_ref().ok_or(BitMaxError::MissingAuth)?;
+
+        let timestamp = timestamp.as_millis();
 
         let prehash = format!("{}+{}", timestamp, &api_path[1..]); // skip the first `/`
-        let mut mac = Hmac::<Sha256>::new_varkey(&auth.private_key_bytes)
-            .map_err(|e| failure::format_err!("{}", e))?;
-        mac.update(prehash.as_bytes());
-        let signature = base64::encode(mac.finalize().into_bytes());
+        let signature = base64::encode(auth.signer.sign(prehash.as_bytes()).await?);
 
         Ok(builder
             .add_header("x-auth-key", &auth.public_key)
@@ -90,29 +232,80 @@ impl BitMaxClient {
 
     fn render_url(
         &self,
-        protocol: &str,
+        base_url: &str,
         endpoint: &str,
         add_account_group: bool,
-    ) -> Fallible<String> {
+    ) -> Result<String> {
         Ok(if add_account_group {
             format!(
-                "{}://{}/{}{}{}",
-                protocol,
-                HTTP_URL,
+                "{}/{}{}{}",
+                base_url,
                 self.auth
                     .as_ref()
-                    .and_then(|a| a.account_group.as_ref())
-                    .ok_or_else(|| failure::format_err!("missing account group"))?,
+                    .and_then(|a| a.account_group.get())
+                    .ok_or(BitMaxError::MissingAccountGroup)?,
                 API_URL,
                 endpoint
             )
         } else {
-            format!("{}://{}{}{}", protocol, HTTP_URL, API_URL, endpoint)
+            format!("{}{}{}", base_url, API_URL, endpoint)
         })
     }
 
-    pub async fn request<Q: Request>(&self, request: Q) -> Fallible<Q::Response> {
-        let url = self.render_url("https", &request.render_endpoint(), Q::NEEDS_ACCOUNT_GROUP)?;
+    pub async fn request<Q: Request>(&self, mut request: Q) -> Result<Q::Response> {
+        request.validate()?;
+
+        if Q::NEEDS_ACCOUNT_GROUP {
+            self.account_group().await?;
+        }
+
+        self.send_with_retry(&mut request).await
+    }
+
+    /// Sends the request, retrying it according to the retry policy
+    async fn send_with_retry<Q: Request>(&self, request: &mut Q) -> Result<Q::Response> {
+        let retryable = Q::METHOD == Method::GET
+            || (self.retry_policy.retry_with_client_id && request.client_id().is_some());
+
+        let mut retry = 0;
+        loop {
+            match self.send_request(request).await {
+                Err(e)
+                    if retryable
+                        && e.is_retryable()
+                        && retry + 1 < self.retry_policy.max_attempts =>
+                {
+                    let delay = self.retry_policy.delay(retry);
+                    warn!(
+                        "{} {} failed: {}, retrying in {:?}",
+                        Q::METHOD,
+                        Q::API_PATH,
+                        e,
+                        delay
+                    );
+                    tokio::time::delay_for(delay).await;
+                    retry += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    async fn send_request<Q: Request>(&self, request: &mut Q) -> Result<Q::Response> {
+        // wait before stamping and signing, so the timestamps are fresh when the request is sent
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(Q::ENDPOINT_CLASS).await;
+        }
+
+        // the same time is used in the body and in the signature
+        let timestamp = Timestamp::from(self.server_time());
+        request.set_timestamp(timestamp);
+
+        let url = self.render_url(
+            &self.base_url,
+            &request.render_endpoint(),
+            Q::NEEDS_ACCOUNT_GROUP,
+        )?;
 
         let req = match Q::METHOD {
             Method::GET => {
@@ -120,58 +313,71 @@ impl BitMaxClient {
                 debug!("sending GET message: {:?}", url.as_str());
                 self.client.request(Q::METHOD, url.as_str())
             }
-            Method::POST | Method::DELETE => {
+            _ => {
                 debug!(
-                    "sending POST message: {:?}",
-                    serde_json::to_string(&request)
+                    "sending {} message: {:?}",
+                    Q::METHOD,
+                    serde_json::to_string(request)
                 );
                 self.client
                     .request(Q::METHOD, url.as_str())
-                    .body(serde_json::to_string(&request)?)
+                    .body(serde_json::to_string(request).map_err(BitMaxError::Serialize)?)
                     .header("content-type", "application/json")
             }
-            _ => failure::bail!("unsupported method {}", Q::METHOD),
         };
 
-        let req = req.header("user-agent", "bitmax-rs");
+        let req = req.header("user-agent", &self.user_agent);
 
         let req = if Q::NEEDS_AUTH {
-            self.attach_auth_headers(req, Q::API_PATH)?
+            self.attach_auth_headers(req, Q::API_PATH, timestamp)
+                .await?
         } else {
             req
         };
 
-        self.handle_response(req.send().await?).await
+        let sent = Utc::now();
+        let resp = req.send().await?;
+        if let Some(date) = resp
+            .headers()
+            .get(DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        {
+            self.clock
+                .record(sent, Utc::now(), date.with_timezone(&Utc));
+        }
+
+        self.handle_response(resp).await
     }
 
     async fn handle_response<T: DeserializeOwned + std::fmt::Debug>(
         &self,
         resp: Response,
-    ) -> Fallible<T> {
+    ) -> Result<T> {
         if resp.status().is_success() {
             let resp = resp.text().await?;
             debug!("got message: {}", &resp);
-            match from_str::<ResponseSchema<T>>(&resp) {
-                Ok(resp) => {
-                    if resp.code == 0 {
-                        Ok(resp.data)
-                    } else {
-                        Err(failure::format_err!("Non zero response code: {:?}", resp))
-                    }
-                }
-                Err(e) => Err(failure::format_err!(
-                    "error {} while deserializing {}",
-                    e,
-                    resp
-                )),
+            match from_str::<ResponseCode>(&resp) {
+                Ok(ResponseCode { code }) if code != 0 => Err(match from_str::<ApiError>(&resp) {
+                    Ok(e) => BitMaxError::Api(e),
+                    Err(error) => BitMaxError::Deserialize {
+                        error,
+                        payload: resp,
+                    },
+                }),
+                _ => match from_str::<ResponseSchema<T>>(&resp) {
+                    Ok(resp) => Ok(resp.data),
+                    Err(error) => Err(BitMaxError::Deserialize {
+                        error,
+                        payload: resp,
+                    }),
+                },
             }
         } else {
-            let resp_e = resp.error_for_status_ref().unwrap_err();
-            Err(failure::format_err!(
-                "error: {}; body: {};",
-                resp_e,
-                resp.text().await?
-            ))
+            Err(BitMaxError::HttpStatus {
+                status: resp.status(),
+                body: resp.text().await?,
+            })
         }
     }
 }
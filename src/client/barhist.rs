@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::{
+    client::{request, BitMaxClient},
+    error::{BitMaxError, Result},
+    model::{BarhistData, Interval, Timestamp},
+};
+
+/// Maximal number of bars returned by a `Barhist` request
+const MAX_BARS: i32 = 500;
+
+/// Fetches the bars of an arbitrary time range, by splitting it into `Barhist` requests
+/// of at most 500 bars, sent concurrently.
+///
+/// `from` and `to` are the start times of the first and last bars, inclusive.
+#[derive(Debug, Clone)]
+pub struct BarhistRange<'a> {
+    pub symbol: &'a str,
+    pub interval: Interval,
+    pub from: Timestamp,
+    pub to: Timestamp,
+    /// Maximal number of requests in flight, they are also subject to the client rate limiter
+    pub concurrency: usize,
+    /// Length of `interval`, fetched with `BarhistInfo` if not set
+    pub interval_in_millis: Option<u64>,
+}
+
+/// Bars sorted by timestamp, without duplicates
+#[derive(Debug, Clone)]
+pub struct BarSeries {
+    pub symbol: String,
+    pub interval: Interval,
+    pub bars: Vec<BarhistData>,
+    /// Missing bars of the requested range, as `(first, last)` missing bar start times,
+    /// inclusive. A range without any bar is a single `(from, to)` gap.
+    /// Not computed for monthly bars, as their length varies.
+    pub gaps: Vec<(Timestamp, Timestamp)>,
+}
+
+impl BarSeries {
+    pub fn is_contiguous(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+
+impl<'a> BarhistRange<'a> {
+    pub fn new(symbol: &'a str, interval: Interval, from: Timestamp, to: Timestamp) -> Self {
+        Self {
+            symbol,
+            interval,
+            from,
+            to,
+            concurrency: 4,
+            interval_in_millis: None,
+        }
+    }
+
+    pub async fn fetch(&self, client: &BitMaxClient) -> Result<BarSeries> {
+        if self.from > self.to {
+            return Err(BitMaxError::InvalidRequest(format!(
+                "bar range starts at {} after its end at {}",
+                self.from, self.to
+            )));
+        }
+
+        let step = match self.interval_in_millis {
+            Some(step) => step,
+            None => client
+                .request(request::BarhistInfo)
+                .await?
+                .into_iter()
+                .find(|info| info.name == self.interval)
+                .map(|info| info.interval_in_millis)
+                .ok_or_else(|| {
+                    BitMaxError::InvalidRequest(format!(
+                        "interval {:?} is not supported by the exchange",
+                        self.interval
+                    ))
+                })?,
+        };
+        if step == 0 {
+            return Err(BitMaxError::InvalidRequest(
+                "interval length must be positive".into(),
+            ));
+        }
+        let step = Duration::milliseconds(step as i64);
+
+        let window = step * MAX_BARS;
+        let windows = (0..)
+            .map(|i| self.from + window * i)
+            .take_while(|start| *start <= self.to)
+            .map(|start| request::Barhist {
+                symbol: self.symbol,
+                interval: self.interval.clone(),
+                from: Some(start),
+                to: Some((start + window - step).min(self.to)),
+                n: Some(MAX_BARS as u32),
+            });
+
+        let pages: Vec<_> = stream::iter(windows)
+            .map(|request| client.request(request))
+            .buffer_unordered(self.concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        // windows may overlap when the exchange aligns the bounds to the bar starts
+        let bars: BTreeMap<_, _> = pages
+            .into_iter()
+            .flatten()
+            .map(|bar| (bar.data.timestamp, bar.data))
+            .filter(|(ts, _)| (self.from..=self.to).contains(ts))
+            .collect();
+        let bars: Vec<_> = bars.into_values().collect();
+
+        let gaps = if self.interval == Interval::T1M {
+            vec![]
+        } else {
+            let starts: Vec<_> = bars.iter().map(|bar| bar.timestamp).collect();
+            gaps(&starts, self.from, self.to, step)
+        };
+
+        Ok(BarSeries {
+            symbol: self.symbol.into(),
+            interval: self.interval.clone(),
+            bars,
+            gaps,
+        })
+    }
+}
+
+/// Missing bars between `from` and `to`, given the sorted start times of the bars found.
+/// Bars are expected every `step` from the ones found, the exchange doesn't align all the
+/// intervals on the epoch, e.g. weekly bars.
+fn gaps(
+    starts: &[Timestamp],
+    from: Timestamp,
+    to: Timestamp,
+    step: Duration,
+) -> Vec<(Timestamp, Timestamp)> {
+    let (head, tail) = match (starts.first(), starts.last()) {
+        (Some(head), Some(tail)) => (*head, *tail),
+        _ => return vec![(from, to)],
+    };
+    let step_ms = step.num_milliseconds();
+    // start times of the first and last bars expected in the range
+    let first = head - step * ((head - from).num_milliseconds() / step_ms) as i32;
+    let last = tail + step * ((to - tail).num_milliseconds() / step_ms) as i32;
+
+    let mut gaps = vec![];
+    if head > first {
+        gaps.push((first, head - step));
+    }
+    gaps.extend(
+        starts
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] > step)
+            .map(|pair| (pair[0] + step, pair[1] - step)),
+    );
+    if tail < last {
+        gaps.push((tail + step, last));
+    }
+
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600_000;
+
+    fn hours(values: &[i64]) -> Vec<Timestamp> {
+        values.iter().map(|h| Timestamp(h * HOUR)).collect()
+    }
+
+    fn gaps_in_hours(starts: &[i64], from: i64, to: i64) -> Vec<(i64, i64)> {
+        gaps(
+            &hours(starts),
+            Timestamp(from),
+            Timestamp(to),
+            Duration::hours(1),
+        )
+        .into_iter()
+        .map(|(first, last)| (first.as_millis() / HOUR, last.as_millis() / HOUR))
+        .collect()
+    }
+
+    #[test]
+    fn contiguous_bars() {
+        assert!(gaps_in_hours(&[1, 2, 3], HOUR, 3 * HOUR).is_empty());
+    }
+
+    #[test]
+    fn inner_gaps() {
+        assert_eq!(
+            gaps_in_hours(&[1, 2, 5, 7], HOUR, 7 * HOUR),
+            vec![(3, 4), (6, 6)]
+        );
+    }
+
+    #[test]
+    fn leading_and_trailing_gaps() {
+        // bounds between bar starts
+        assert_eq!(
+            gaps_in_hours(&[3, 4], HOUR / 2, 6 * HOUR + HOUR / 2),
+            vec![(1, 2), (5, 6)]
+        );
+        // the missing bar at `to` is reported
+        assert_eq!(gaps_in_hours(&[1, 2], HOUR, 3 * HOUR), vec![(3, 3)]);
+    }
+
+    #[test]
+    fn bars_not_aligned_on_the_epoch() {
+        let day = Duration::days(1);
+        let week = Duration::weeks(1);
+        // a Monday, the epoch fell on a Thursday
+        let monday = Timestamp(4 * day.num_milliseconds());
+        let starts = [monday + week, monday + week * 2];
+
+        assert_eq!(
+            gaps(&starts, monday - day, monday + week * 3 - day, week),
+            vec![(monday, monday)]
+        );
+        assert!(gaps(&starts, monday + day, monday + week * 3 - day, week).is_empty());
+    }
+
+    #[test]
+    fn empty_range() {
+        assert_eq!(
+            gaps(
+                &[],
+                Timestamp(HOUR),
+                Timestamp(5 * HOUR),
+                Duration::hours(1)
+            ),
+            vec![(Timestamp(HOUR), Timestamp(5 * HOUR))]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use reqwest::{Certificate, Client, Proxy};
+use zeroize::Zeroizing;
+
+use crate::{
+    client::{
+        AccountGroup, Auth, BitMaxClient, HmacSigner, RateLimiter, RetryPolicy, Signer,
+        DEFAULT_USER_AGENT, DEFAULT_WS_HEARTBEAT_TIMEOUT,
+    },
+    error::Result,
+};
+
+/// Exchange deployment the client talks to
+#[derive(Debug, Clone, Default)]
+pub enum Environment {
+    #[default]
+    Production,
+    Sandbox,
+    /// Any other host, e.g. a regional one or a local mock server
+    /// (`http://127.0.0.1:8080` and `ws://127.0.0.1:8080`)
+    Custom {
+        base_url: String,
+        ws_url: String,
+    },
+}
+
+impl Environment {
+    pub fn base_url(&self) -> &str {
+        match self {
+            Self::Production => "https://bitmax.io",
+            Self::Sandbox => "https://bitmax-test.io",
+            Self::Custom { base_url, .. } => base_url,
+        }
+    }
+
+    pub fn ws_url(&self) -> &str {
+        match self {
+            Self::Production => "wss://bitmax.io",
+            Self::Sandbox => "wss://bitmax-test.io",
+            Self::Custom { ws_url, .. } => ws_url,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum SecretKey {
+    Base64(Zeroizing<String>),
+    Signer(Arc<dyn Signer>),
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64(_) => f.write_str("Base64(..)"),
+            Self::Signer(signer) => f.debug_tuple("Signer").field(signer).finish(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Keys {
+    public_key: String,
+    secret: SecretKey,
+    account_group: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BitMaxClientBuilder {
+    environment: Environment,
+    base_url: Option<String>,
+    ws_url: Option<String>,
+    keys: Option<Keys>,
+    http_client: Option<Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxies: Vec<Proxy>,
+    root_certificates: Vec<Certificate>,
+    user_agent: Option<String>,
+    ws_connect_timeout: Option<Duration>,
+    ws_heartbeat_timeout: Option<Option<Duration>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl BitMaxClientBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Overrides the REST url of the selected environment, e.g. `https://bitmax.io`
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Overrides the websocket url of the selected environment, e.g. `wss://bitmax.io`
+    pub fn ws_url(mut self, ws_url: &str) -> Self {
+        self.ws_url = Some(ws_url.into());
+        self
+    }
+
+    /// `private_key` is the base64 encoded secret, as given by the exchange
+    pub fn auth(mut self, public_key: &str, private_key: &str, account_group: Option<u32>) -> Self {
+        self.keys = Some(Keys {
+            public_key: public_key.into(),
+            secret: SecretKey::Base64(Zeroizing::new(private_key.into())),
+            account_group,
+        });
+        self
+    }
+
+    /// Like `auth`, but requests are signed by `signer` instead of an in-memory key
+    pub fn signer<S: Signer + 'static>(
+        mut self,
+        public_key: &str,
+        signer: S,
+        account_group: Option<u32>,
+    ) -> Self {
+        self.keys = Some(Keys {
+            public_key: public_key.into(),
+            secret: SecretKey::Signer(Arc::new(signer)),
+            account_group,
+        });
+        self
+    }
+
+    /// Use an already configured `reqwest::Client` for REST requests.
+    /// `timeout`, `connect_timeout`, `proxy` and `add_root_certificate` are ignored in this case.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Total timeout of a REST request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Route REST requests through `proxy`. Websockets always connect directly.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Trust an additional root certificate for REST requests, on top of the system ones.
+    /// Websockets only trust the system certificates.
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// User agent sent with REST requests and the websocket handshake, `bitmax-rs` by default
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Timeout of the websocket TCP connection, TLS negotiation and handshake, reported as
+    /// `BitMaxError::Timeout`
+    pub fn ws_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.ws_connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Websockets report `BitMaxError::HeartbeatTimeout` when the exchange doesn't ping them
+    /// for this long, 45 seconds by default. `None` disables the timeout.
+    pub fn ws_heartbeat_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.ws_heartbeat_timeout = Some(timeout);
+        self
+    }
+
+    /// Throttle REST requests client side, the limiter is shared by all clones of the client
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(Arc::new(rate_limiter));
+        self
+    }
+
+    /// Replaces `RetryPolicy::default()`, use `RetryPolicy::none()` to disable retries
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn build(self) -> Result<BitMaxClient> {
+        let auth = match self.keys {
+            Some(keys) => Some(Auth {
+                signer: match keys.secret {
+                    SecretKey::Base64(private_key) => {
+                        Arc::new(HmacSigner::from_base64(&private_key)?)
+                    }
+                    SecretKey::Signer(signer) => signer,
+                },
+                public_key: keys.public_key,
+                account_group: Arc::new(AccountGroup::new(keys.account_group)),
+            }),
+            None => None,
+        };
+
+        let client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder();
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                for certificate in self.root_certificates {
+                    builder = builder.add_root_certificate(certificate);
+                }
+                builder.build()?
+            }
+        };
+
+        let environment = self.environment;
+        let base_url = self
+            .base_url
+            .unwrap_or_else(|| environment.base_url().into());
+        let ws_url = self.ws_url.unwrap_or_else(|| environment.ws_url().into());
+
+        Ok(BitMaxClient {
+            client,
+            auth,
+            base_url: base_url.trim_end_matches('/').into(),
+            ws_url: ws_url.trim_end_matches('/').into(),
+            user_agent: self.user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.into()),
+            ws_connect_timeout: self.ws_connect_timeout,
+            ws_heartbeat_timeout: self
+                .ws_heartbeat_timeout
+                .unwrap_or(Some(DEFAULT_WS_HEARTBEAT_TIMEOUT)),
+            rate_limiter: self.rate_limiter,
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            clock: Default::default(),
+        })
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Estimates are dropped after this time, so that the clock drift is followed
+const MAX_ESTIMATE_AGE_SECS: i64 = 600;
+
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    // bounds of `server time - local time`, in milliseconds
+    lower: i64,
+    upper: i64,
+    since: DateTime<Utc>,
+}
+
+/// Estimates the offset of the exchange clock from the local one.
+///
+/// The `Date` header of every response has a second precision, but it bounds the offset:
+/// the server time at the moment it answered is within `[date, date + 1s)`, and that moment is
+/// between sending the request and receiving the response. Intersecting the bounds given by
+/// successive responses narrows the estimate down.
+#[derive(Debug, Default)]
+pub(crate) struct ClockSync {
+    estimate: Mutex<Option<Estimate>>,
+}
+
+impl ClockSync {
+    pub fn record(&self, sent: DateTime<Utc>, received: DateTime<Utc>, date: DateTime<Utc>) {
+        let date = date.timestamp_millis();
+        let lower = date - received.timestamp_millis();
+        let upper = date + 1000 - sent.timestamp_millis();
+
+        let mut estimate = self.estimate.lock().unwrap();
+        *estimate = match *estimate {
+            Some(e)
+                if e.lower.max(lower) <= e.upper.min(upper)
+                    && (received - e.since).num_seconds() < MAX_ESTIMATE_AGE_SECS =>
+            {
+                Some(Estimate {
+                    lower: e.lower.max(lower),
+                    upper: e.upper.min(upper),
+                    since: e.since,
+                })
+            }
+            _ => Some(Estimate {
+                lower,
+                upper,
+                since: received,
+            }),
+        };
+    }
+
+    /// `server time - local time`, `None` until a response was received
+    pub fn offset(&self) -> Option<Duration> {
+        self.estimate
+            .lock()
+            .unwrap()
+            .map(|e| Duration::milliseconds((e.lower + e.upper) / 2))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset().unwrap_or_else(Duration::zero)
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, env, fmt, fs, path::Path};
+
+use serde::Deserialize;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    client::{BitMaxClient, BitMaxClientBuilder},
+    error::{BitMaxError, Result},
+};
+
+const PUBLIC_KEY_VAR: &str = "BITMAX_PUBLIC";
+const PRIVATE_KEY_VAR: &str = "BITMAX_PRIVATE";
+const ACCOUNT_GROUP_VAR: &str = "BITMAX_ACCOUNT_GROUP";
+
+/// Api keys of an account, the private key is wiped from memory on drop.
+///
+/// Credentials files hold one table per profile (e.g. per sub-account), in TOML:
+/// ```toml
+/// [default]
+/// public_key = "..."
+/// private_key = "..."
+/// account_group = 6 # optional
+/// ```
+/// or in JSON, if the file name ends with `.json`.
+#[derive(Deserialize, Clone)]
+pub struct Credentials {
+    pub public_key: String,
+    /// Base64 encoded secret, as given by the exchange
+    pub private_key: String,
+    #[serde(default)]
+    pub account_group: Option<u32>,
+}
+
+impl Credentials {
+    pub fn new(public_key: &str, private_key: &str, account_group: Option<u32>) -> Self {
+        Self {
+            public_key: public_key.into(),
+            private_key: private_key.into(),
+            account_group,
+        }
+    }
+
+    /// Reads `BITMAX_PUBLIC`, `BITMAX_PRIVATE` and, optionally, `BITMAX_ACCOUNT_GROUP`
+    pub fn from_env() -> Result<Self> {
+        let var = |name| {
+            env::var(name).map_err(|e| BitMaxError::InvalidCredentials(format!("{}: {}", name, e)))
+        };
+
+        let account_group = match env::var(ACCOUNT_GROUP_VAR) {
+            Ok(group) => Some(group.parse().map_err(|e| {
+                BitMaxError::InvalidCredentials(format!("{}: {}", ACCOUNT_GROUP_VAR, e))
+            })?),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            public_key: var(PUBLIC_KEY_VAR)?,
+            private_key: var(PRIVATE_KEY_VAR)?,
+            account_group,
+        })
+    }
+
+    /// Loads all the profiles of a credentials file
+    pub fn profiles_from_file<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Self>> {
+        let path = path.as_ref();
+        // the file holds the private keys, wiped like the credentials
+        let contents = Zeroizing::new(fs::read_to_string(path)?);
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents)
+                .map_err(|e| BitMaxError::InvalidCredentials(format!("{}: {}", path.display(), e)))
+        } else {
+            toml::from_str(&contents)
+                .map_err(|e| BitMaxError::InvalidCredentials(format!("{}: {}", path.display(), e)))
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, profile: &str) -> Result<Self> {
+        let path = path.as_ref();
+        Self::profiles_from_file(path)?
+            .remove(profile)
+            .ok_or_else(|| {
+                BitMaxError::InvalidCredentials(format!(
+                    "no profile {:?} in {}",
+                    profile,
+                    path.display()
+                ))
+            })
+    }
+
+    /// Builds an authenticated client. If the account group is missing, it is fetched
+    /// with the `AccountInfo` request and cached in the credentials
+    pub async fn client(&mut self) -> Result<BitMaxClient> {
+        self.client_with(BitMaxClient::builder()).await
+    }
+
+    /// Same as `client`, with the other client settings taken from `builder`
+    pub async fn client_with(&mut self, builder: BitMaxClientBuilder) -> Result<BitMaxClient> {
+        let client = builder.credentials(self).build()?;
+        self.account_group = Some(client.account_group().await?);
+
+        Ok(client)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("public_key", &self.public_key)
+            .field("account_group", &self.account_group)
+            .finish()
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        self.private_key.zeroize();
+    }
+}
+
+impl BitMaxClientBuilder {
+    pub fn credentials(self, credentials: &Credentials) -> Self {
+        self.auth(
+            &credentials.public_key,
+            &credentials.private_key,
+            credentials.account_group,
+        )
+    }
+}
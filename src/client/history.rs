@@ -0,0 +1,70 @@
+use futures::{stream, Stream, TryStreamExt};
+
+use crate::{
+    client::{request, BitMaxClient},
+    error::{BitMaxError, Result},
+    model::{HistoryOrder, TransactionHistoryEntry},
+};
+
+impl BitMaxClient {
+    /// Orders matching `filter`, fetched page by page as the stream is polled.
+    /// The stream starts at `filter.page`, if set, and ends after the last page.
+    pub fn order_history_stream<'a>(
+        &'a self,
+        filter: request::OrderHistory<'a>,
+    ) -> impl Stream<Item = Result<HistoryOrder>> + 'a {
+        paginate(filter.page, move |page| {
+            let request = request::OrderHistory {
+                page: Some(page),
+                ..filter.clone()
+            };
+
+            async move {
+                let response = self.request(request).await?;
+                Ok((response.data, response.has_next))
+            }
+        })
+    }
+
+    /// Deposits and withdrawals matching `filter`, fetched page by page as the stream
+    /// is polled. The stream starts at `filter.page`, if set, and ends after the last page.
+    pub fn transaction_history_stream<'a>(
+        &'a self,
+        filter: request::TransactionHistory<'a>,
+    ) -> impl Stream<Item = Result<TransactionHistoryEntry>> + 'a {
+        paginate(filter.page, move |page| {
+            let request = request::TransactionHistory {
+                page: Some(page),
+                ..filter.clone()
+            };
+
+            async move {
+                let response = self.request(request).await?;
+                Ok((response.data, response.has_next))
+            }
+        })
+    }
+}
+
+/// Flattens the pages returned by `fetch`, given a page number starting at 1,
+/// which returns the page items and whether there is a next page
+fn paginate<T, F, Fut>(first: Option<u32>, mut fetch: F) -> impl Stream<Item = Result<T>>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<(Vec<T>, bool)>>,
+{
+    stream::try_unfold(Some(first.unwrap_or(1)), move |page| {
+        let next = page.map(&mut fetch);
+
+        async move {
+            let (items, has_next) = match next {
+                Some(next) => next.await?,
+                None => return Ok::<_, BitMaxError>(None),
+            };
+            let next_page = if has_next { page.map(|p| p + 1) } else { None };
+
+            Ok(Some((stream::iter(items.into_iter().map(Ok)), next_page)))
+        }
+    })
+    .try_flatten()
+}
@@ -0,0 +1,104 @@
+use std::time::Instant;
+
+use futures::StreamExt;
+use log::warn;
+
+use crate::{
+    client::{
+        request,
+        websocket::{Subscription, SubscriptionManager},
+        BitMaxClient, RetryPolicy,
+    },
+    error::{BitMaxError, Result},
+    model::{websocket::DepthData, OrderBook},
+};
+
+/// Order book kept in sync with a depth subscription, a snapshot is fetched with the
+/// `OrderDepth` request on start and whenever updates are missed.
+///
+/// While the snapshots lag behind the updates, they are fetched again with the backoff
+/// of `RetryPolicy::default`, updates are buffered in the meantime.
+pub struct OrderBookFeed {
+    client: BitMaxClient,
+    depth: Subscription<DepthData>,
+    book: OrderBook,
+    backoff: RetryPolicy,
+    failed_resyncs: u32,
+    next_resync: Instant,
+}
+
+impl OrderBookFeed {
+    pub async fn new(
+        client: &BitMaxClient,
+        subscriptions: &SubscriptionManager,
+        symbol: &str,
+    ) -> Result<Self> {
+        let mut depth = subscriptions.subscribe_depth(symbol)?;
+        // the snapshot must not be older than the first update
+        depth.confirmed().await?;
+
+        let mut feed = Self {
+            client: client.clone(),
+            depth,
+            book: OrderBook::new(symbol),
+            backoff: RetryPolicy::default(),
+            failed_resyncs: 0,
+            next_resync: Instant::now(),
+        };
+        feed.resync().await?;
+
+        Ok(feed)
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// Applies the next depth update, `None` once the websocket is closed.
+    /// Errors are transient, the snapshot is fetched again on a later update.
+    pub async fn next(&mut self) -> Option<Result<&OrderBook>> {
+        let update = self.depth.next().await?;
+
+        if let Err(e) = self.book.apply_update(&update) {
+            warn!("{} order book out of sync: {}", self.book.symbol(), e);
+        }
+        if !self.book.is_synced() && Instant::now() >= self.next_resync {
+            if let Err(e) = self.resync().await {
+                return Some(Err(e));
+            }
+        }
+
+        Some(Ok(&self.book))
+    }
+
+    /// Fetches a snapshot, the next one is delayed if it didn't bring the book in sync
+    async fn resync(&mut self) -> Result<()> {
+        let snapshot = self
+            .client
+            .request(request::OrderDepth {
+                symbol: self.book.symbol(),
+            })
+            .await;
+
+        let result = snapshot.and_then(|snapshot| self.book.apply_rest_snapshot(&snapshot.data));
+        if self.book.is_synced() {
+            self.failed_resyncs = 0;
+        } else {
+            self.next_resync = Instant::now() + self.backoff.delay(self.failed_resyncs);
+            self.failed_resyncs = self.failed_resyncs.saturating_add(1);
+        }
+
+        match result {
+            // updates missed during the replay are fetched with the next snapshot
+            Err(e @ BitMaxError::SequenceGap { .. }) => {
+                warn!(
+                    "{} snapshot is behind the updates: {}",
+                    self.book.symbol(),
+                    e
+                );
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
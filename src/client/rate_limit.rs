@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::error::{BitMaxError, Result};
+
+/// Longest time a request waits for the budget to be refilled
+const MAX_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Group of endpoints sharing the same request budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointClass {
+    /// Market data, no auth needed
+    Public,
+    /// Authenticated requests that don't create or cancel orders
+    Account,
+    /// Order placement and cancellation
+    Order,
+}
+
+/// Token bucket parameters: up to `burst` requests at once,
+/// refilled at `per_second` requests per second
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Quota {
+    /// Fails if `per_second` is not a positive number
+    pub fn new(burst: u32, per_second: f64) -> Result<Self> {
+        let quota = Self { burst, per_second };
+        quota.validate()?;
+        Ok(quota)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.per_second > 0. && self.per_second.is_finite() {
+            Ok(())
+        } else {
+            Err(BitMaxError::InvalidRequest(format!(
+                "quota refill rate must be positive, got {} per second",
+                self.per_second
+            )))
+        }
+    }
+}
+
+/// Snapshot of the budget of an endpoint class
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    pub quota: Quota,
+    /// Requests that can be sent right now without waiting, negative if requests are queued
+    pub available: f64,
+    /// Number of requests that went through the limiter
+    pub requests: u64,
+    /// Number of requests that had to wait
+    pub throttled: u64,
+    /// Total time spent waiting
+    pub throttled_time: Duration,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated: Instant,
+    requests: u64,
+    throttled: u64,
+    throttled_time: Duration,
+}
+
+impl Bucket {
+    fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            tokens: quota.burst as f64,
+            updated: Instant::now(),
+            requests: 0,
+            throttled: 0,
+            throttled_time: Duration::default(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.quota.per_second).min(self.quota.burst as f64);
+        self.updated = now;
+    }
+
+    /// Takes a token, returns how long the caller has to wait before it may use it
+    fn reserve(&mut self) -> Duration {
+        self.refill(Instant::now());
+        self.tokens -= 1.;
+        self.requests += 1;
+
+        if self.tokens >= 0. {
+            return Duration::default();
+        }
+
+        // quotas are validated, this only keeps an absurdly low rate from overflowing
+        let wait = (-self.tokens / self.quota.per_second).min(MAX_WAIT.as_secs_f64());
+        let wait = Duration::try_from_secs_f64(wait).unwrap_or(MAX_WAIT);
+        self.throttled += 1;
+        self.throttled_time += wait;
+        wait
+    }
+
+    fn budget(&mut self) -> Budget {
+        self.refill(Instant::now());
+        Budget {
+            quota: self.quota,
+            available: self.tokens,
+            requests: self.requests,
+            throttled: self.throttled,
+            throttled_time: self.throttled_time,
+        }
+    }
+}
+
+/// Client side rate limiter, requests exceeding the budget of their
+/// endpoint class wait until the budget is refilled instead of failing.
+/// Classes without a quota are not limited.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<EndpointClass, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Fails if the fields of `quota` were set to an invalid refill rate
+    pub fn with_quota(self, class: EndpointClass, quota: Quota) -> Result<Self> {
+        quota.validate()?;
+        self.buckets
+            .lock()
+            .unwrap()
+            .insert(class, Bucket::new(quota));
+        Ok(self)
+    }
+
+    pub async fn acquire(&self, class: EndpointClass) {
+        let wait = match self.buckets.lock().unwrap().get_mut(&class) {
+            Some(bucket) => bucket.reserve(),
+            None => return,
+        };
+
+        if wait > Duration::default() {
+            debug!("rate limit for {:?} reached, waiting {:?}", class, wait);
+            tokio::time::delay_for(wait).await;
+        }
+    }
+
+    pub fn budget(&self, class: EndpointClass) -> Option<Budget> {
+        self.buckets
+            .lock()
+            .unwrap()
+            .get_mut(&class)
+            .map(Bucket::budget)
+    }
+}
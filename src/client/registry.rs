@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use tokio::task::JoinHandle;
+
+use crate::{
+    client::{
+        request::{self, PlaceOrder},
+        BitMaxClient,
+    },
+    error::Result,
+    model::{self, AccountType, Asset, Product, ProductRules, RoundingMode},
+};
+
+/// Products and assets listed on the exchange at some point in time
+#[derive(Debug, Clone)]
+pub struct Instruments {
+    rules: ProductRules,
+    assets: HashMap<String, Asset>,
+    // symbols of the products, by base and by quote asset
+    by_base: HashMap<String, Vec<String>>,
+    by_quote: HashMap<String, Vec<String>>,
+    updated: DateTime<Utc>,
+}
+
+impl Instruments {
+    pub fn new(products: Vec<Product>, assets: Vec<Asset>) -> Self {
+        let mut by_base: HashMap<_, Vec<_>> = HashMap::new();
+        let mut by_quote: HashMap<_, Vec<_>> = HashMap::new();
+        for product in &products {
+            by_base
+                .entry(product.base_asset.clone())
+                .or_default()
+                .push(product.symbol.clone());
+            by_quote
+                .entry(product.quote_asset.clone())
+                .or_default()
+                .push(product.symbol.clone());
+        }
+
+        Self {
+            rules: ProductRules::new(products),
+            assets: assets
+                .into_iter()
+                .map(|a| (a.asset_code.clone(), a))
+                .collect(),
+            by_base,
+            by_quote,
+            updated: Utc::now(),
+        }
+    }
+
+    pub fn product(&self, symbol: &str) -> Option<&Product> {
+        self.rules.get(symbol)
+    }
+
+    pub fn asset(&self, asset_code: &str) -> Option<&Asset> {
+        self.assets.get(asset_code)
+    }
+
+    pub fn products(&self) -> impl Iterator<Item = &Product> {
+        self.rules.products()
+    }
+
+    pub fn assets(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.values()
+    }
+
+    /// Products trading `asset` against other assets
+    pub fn products_by_base<'a>(&'a self, asset: &str) -> impl Iterator<Item = &'a Product> {
+        self.lookup(&self.by_base, asset)
+    }
+
+    /// Products priced in `asset`
+    pub fn products_by_quote<'a>(&'a self, asset: &str) -> impl Iterator<Item = &'a Product> {
+        self.lookup(&self.by_quote, asset)
+    }
+
+    /// Trading rules, to check and round orders
+    pub fn rules(&self) -> &ProductRules {
+        &self.rules
+    }
+
+    /// Local time of the requests the instruments were loaded from
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.updated
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        index: &'a HashMap<String, Vec<String>>,
+        asset: &str,
+    ) -> impl Iterator<Item = &'a Product> {
+        index
+            .get(asset)
+            .into_iter()
+            .flatten()
+            .filter_map(move |symbol| self.rules.get(symbol))
+    }
+}
+
+/// Balance of an asset along with its listing, e.g. for its name and precision
+#[derive(Debug, Clone)]
+pub struct AssetBalance {
+    pub balance: model::Balance,
+    /// `None` if the asset is not listed anymore
+    pub asset: Option<Asset>,
+}
+
+/// Shared cache of the `Products` and `Assets` listings.
+///
+/// Clones share the same cache. Readers take a `snapshot`, which stays consistent while
+/// the registry is refreshed in the background.
+///
+/// It checks orders with `check` and `conform`, subscribed symbols once given to
+/// `SubscriptionManager::with_registry`, and labels balances with `balances`.
+#[derive(Debug, Clone)]
+pub struct InstrumentRegistry {
+    client: BitMaxClient,
+    instruments: Arc<RwLock<Arc<Instruments>>>,
+}
+
+impl InstrumentRegistry {
+    /// Loads the products and the assets with `client`, which is also used for refreshing
+    pub async fn load(client: BitMaxClient) -> Result<Self> {
+        let instruments = Self::fetch(&client).await?;
+
+        Ok(Self {
+            client,
+            instruments: Arc::new(RwLock::new(Arc::new(instruments))),
+        })
+    }
+
+    pub fn snapshot(&self) -> Arc<Instruments> {
+        self.instruments.read().unwrap().clone()
+    }
+
+    pub fn product(&self, symbol: &str) -> Option<Product> {
+        self.snapshot().product(symbol).cloned()
+    }
+
+    pub fn asset(&self, asset_code: &str) -> Option<Asset> {
+        self.snapshot().asset(asset_code).cloned()
+    }
+
+    /// Non-zero balances of the account, with the listing of their assets
+    pub async fn balances(&self, account_type: AccountType) -> Result<Vec<AssetBalance>> {
+        let balances = self
+            .client
+            .request(request::Balance {
+                account_type,
+                asset: None,
+                show_all: false,
+            })
+            .await?;
+        let instruments = self.snapshot();
+
+        Ok(balances
+            .into_iter()
+            .map(|balance| AssetBalance {
+                asset: instruments.asset(&balance.asset).cloned(),
+                balance,
+            })
+            .collect())
+    }
+
+    /// Checks the order against the current trading rules, see `ProductRules::check`
+    pub fn check(&self, order: &PlaceOrder<'_>) -> Result<()> {
+        self.snapshot().rules().check(order)
+    }
+
+    /// Rounds the order to the current trading rules, see `ProductRules::conform`
+    pub fn conform<'a>(
+        &self,
+        order: PlaceOrder<'a>,
+        price_mode: RoundingMode,
+        qty_mode: RoundingMode,
+    ) -> Result<PlaceOrder<'a>> {
+        self.snapshot().rules().conform(order, price_mode, qty_mode)
+    }
+
+    pub async fn refresh(&self) -> Result<()> {
+        let instruments = Self::fetch(&self.client).await?;
+        *self.instruments.write().unwrap() = Arc::new(instruments);
+
+        Ok(())
+    }
+
+    /// Refreshes the registry every `interval`, until all the clones of the registry are
+    /// dropped. Failed refreshes are logged and the previous listings are kept.
+    pub fn spawn_refresh(&self, interval: Duration) -> JoinHandle<()> {
+        let client = self.client.clone();
+        let instruments = Arc::downgrade(&self.instruments);
+
+        tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + interval;
+            let mut ticks = tokio::time::interval_at(start, interval);
+            loop {
+                ticks.tick().await;
+                let fetched = Self::fetch(&client).await;
+                let instruments = match instruments.upgrade() {
+                    Some(instruments) => instruments,
+                    None => return,
+                };
+                match fetched {
+                    Ok(fetched) => *instruments.write().unwrap() = Arc::new(fetched),
+                    Err(e) => warn!("failed to refresh the instruments: {}", e),
+                }
+            }
+        })
+    }
+
+    async fn fetch(client: &BitMaxClient) -> Result<Instruments> {
+        let (products, assets) = futures::try_join!(
+            client.request(request::Products),
+            client.request(request::Assets)
+        )?;
+
+        Ok(Instruments::new(products, assets))
+    }
+}
@@ -1,7 +1,13 @@
 use reqwest::Method;
-use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde::{
+    de::DeserializeOwned, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer,
+};
 
-use crate::model::{self, AccountType, Fixed9};
+use crate::{
+    client::EndpointClass,
+    error::{BitMaxError, Result},
+    model::{self, AccountType, AssetStatus, ClientOrderId, Fixed9, Timestamp},
+};
 
 pub trait Request: Serialize {
     type Response: DeserializeOwned + std::fmt::Debug;
@@ -10,11 +16,31 @@ pub trait Request: Serialize {
     const NEEDS_ACCOUNT_GROUP: bool;
     const NEEDS_AUTH: bool;
     const API_PATH: &'static str;
+    /// Rate limit budget the request is accounted against
+    const ENDPOINT_CLASS: EndpointClass = if Self::NEEDS_AUTH {
+        EndpointClass::Account
+    } else {
+        EndpointClass::Public
+    };
 
     fn account_type(&self) -> Option<model::AccountType> {
         None
     }
 
+    /// Client generated id, lets the exchange detect duplicates when the request is retried
+    fn client_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Checks the request before it is sent, to reject malformed requests locally
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before every attempt to send the request, once rate limited, with the
+    /// exchange time also used to sign it, for requests carrying a timestamp
+    fn set_timestamp(&mut self, _timestamp: Timestamp) {}
+
     fn render_endpoint(&self) -> String {
         match self.account_type() {
             None => Self::API_PATH.into(),
@@ -28,7 +54,7 @@ pub trait Request: Serialize {
 pub struct Dummy;
 
 impl<'de> Deserialize<'de> for Dummy {
-    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
+    fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -135,9 +161,9 @@ impl Request for BarhistInfo {
 pub struct Barhist<'a> {
     pub symbol: &'a str,
     pub interval: model::Interval,
-    pub from: Option<i64>, // unix timestamp in !MILLISECONDS!
-    pub to: Option<i64>,   // unix timestamp in !MILLISECONDS!
-    pub n: Option<u32>,    // number of bars to be returned
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub n: Option<u32>, // number of bars to be returned
 }
 
 impl Request for Barhist<'_> {
@@ -191,7 +217,7 @@ impl Request for AccountInfo {
     const API_PATH: &'static str = "/info";
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Copy, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Balance<'a> {
     #[serde(skip)]
@@ -213,9 +239,16 @@ impl Request for Balance<'_> {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct MarginRisk;
 
+// serialized as empty arguments rather than null, for the websocket request
+impl Serialize for MarginRisk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_map(Some(0))?.end()
+    }
+}
+
 impl Request for MarginRisk {
     type Response = model::MarginRisk;
 
@@ -276,6 +309,80 @@ impl Request for TransactionHistory<'_> {
     const API_PATH: &'static str = "/wallet/transactions";
 }
 
+/// Withdraw an asset to an external address.
+/// Use `check_asset` with the asset listed by the `Assets` request to catch invalid
+/// withdrawals before they are sent, and `BitMaxClient::wait_for_transaction` to follow the withdrawal.
+#[serde_with::skip_serializing_none]
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Withdraw<'a> {
+    pub asset: &'a str,
+    pub amount: Fixed9, // fee included
+    pub address: &'a str,
+    pub dest_tag: Option<&'a str>,
+    pub blockchain: Option<&'a str>,
+}
+
+impl Withdraw<'_> {
+    /// Checks the withdrawal against the rules of its asset, on top of `validate`
+    pub fn check_asset(&self, asset: &model::Asset) -> Result<()> {
+        let invalid = |reason: String| Err(BitMaxError::InvalidRequest(reason));
+
+        self.validate()?;
+        if asset.asset_code != self.asset {
+            return invalid(format!(
+                "asset info for {} given to validate a {} withdrawal",
+                asset.asset_code, self.asset
+            ));
+        }
+        if !matches!(asset.status, AssetStatus::Normal | AssetStatus::NoDeposit) {
+            return invalid(format!(
+                "{} withdrawals are disabled: {:?}",
+                self.asset, asset.status
+            ));
+        }
+        if self.amount < asset.min_withdrawal_amt {
+            return invalid(format!(
+                "amount {} is below the minimum {} withdrawal of {}",
+                self.amount, self.asset, asset.min_withdrawal_amt
+            ));
+        }
+        if self.amount <= asset.withdrawal_fee {
+            return invalid(format!(
+                "amount {} doesn't cover the {} withdrawal fee of {}",
+                self.amount, self.asset, asset.withdrawal_fee
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Request for Withdraw<'_> {
+    type Response = model::WithdrawResponse;
+
+    const METHOD: Method = Method::POST;
+    const NEEDS_ACCOUNT_GROUP: bool = false;
+    const NEEDS_AUTH: bool = true;
+    const API_PATH: &'static str = "/wallet/withdraw";
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(BitMaxError::InvalidRequest(reason));
+
+        if self.asset.is_empty() || self.address.is_empty() {
+            return invalid(format!(
+                "asset and address must be set, got {:?} to {:?}",
+                self.asset, self.address
+            ));
+        }
+        if self.amount <= Fixed9(0) {
+            return invalid(format!("amount must be positive, got {}", self.amount));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Clone, Copy, Debug)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum ResponseInstruction {
@@ -287,18 +394,21 @@ pub enum ResponseInstruction {
 
 /// Refer to https://bitmax-exchange.github.io/bitmax-pro-api/#place-order
 /// in order to ensure that your request is well-formed.
+///
+/// Prefer the `limit`, `market`, `stop_limit` and `stop_market` constructors to filling
+/// the fields by hand, malformed combinations are rejected by `validate` before sending.
 #[serde_with::skip_serializing_none]
-#[derive(Serialize, Clone, Copy, Debug)]
+#[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaceOrder<'a> {
     #[serde(skip)]
     pub account_type: AccountType,
     pub symbol: &'a str,
-    pub time: i64,
+    pub time: Timestamp, // set by `BitMaxClient::request`, sent as is by websockets
     pub order_qty: Fixed9,
     pub order_type: model::OrderType,
     pub side: model::OrderSide,
-    pub id: Option<&'a str>,
+    pub id: Option<ClientOrderId>,
     pub order_price: Option<Fixed9>,
     pub stop_price: Option<Fixed9>,
     pub post_only: Option<bool>,
@@ -306,6 +416,102 @@ pub struct PlaceOrder<'a> {
     pub resp_inst: ResponseInstruction,
 }
 
+impl<'a> PlaceOrder<'a> {
+    /// Cash GTC limit order with a random client id
+    pub fn limit(symbol: &'a str, side: model::OrderSide, qty: Fixed9, price: Fixed9) -> Self {
+        Self {
+            order_price: Some(price),
+            ..Self::new(symbol, side, qty, model::OrderType::Limit)
+        }
+    }
+
+    /// Cash market order with a random client id
+    pub fn market(symbol: &'a str, side: model::OrderSide, qty: Fixed9) -> Self {
+        Self::new(symbol, side, qty, model::OrderType::Market)
+    }
+
+    /// Cash GTC limit order placed once the market reaches `stop_price`
+    pub fn stop_limit(
+        symbol: &'a str,
+        side: model::OrderSide,
+        qty: Fixed9,
+        price: Fixed9,
+        stop_price: Fixed9,
+    ) -> Self {
+        Self {
+            order_price: Some(price),
+            stop_price: Some(stop_price),
+            ..Self::new(symbol, side, qty, model::OrderType::StopLimit)
+        }
+    }
+
+    /// Cash market order placed once the market reaches `stop_price`
+    pub fn stop_market(
+        symbol: &'a str,
+        side: model::OrderSide,
+        qty: Fixed9,
+        stop_price: Fixed9,
+    ) -> Self {
+        Self {
+            stop_price: Some(stop_price),
+            ..Self::new(symbol, side, qty, model::OrderType::StopMarket)
+        }
+    }
+
+    fn new(
+        symbol: &'a str,
+        side: model::OrderSide,
+        qty: Fixed9,
+        order_type: model::OrderType,
+    ) -> Self {
+        Self {
+            account_type: AccountType::Cash,
+            symbol,
+            time: Timestamp::now(),
+            order_qty: qty,
+            order_type,
+            side,
+            id: Some(ClientOrderId::new()),
+            order_price: None,
+            stop_price: None,
+            post_only: None,
+            time_in_force: model::TimeInForce::GTC,
+            resp_inst: ResponseInstruction::Acknowledged,
+        }
+    }
+
+    pub fn with_account_type(self, account_type: AccountType) -> Self {
+        Self {
+            account_type,
+            ..self
+        }
+    }
+
+    /// Replaces the random client id, `None` lets the exchange generate one
+    pub fn with_id(self, id: Option<ClientOrderId>) -> Self {
+        Self { id, ..self }
+    }
+
+    pub fn with_time_in_force(self, time_in_force: model::TimeInForce) -> Self {
+        Self {
+            time_in_force,
+            ..self
+        }
+    }
+
+    /// Reject the order instead of matching it immediately, limit GTC orders only
+    pub fn with_post_only(self, post_only: bool) -> Self {
+        Self {
+            post_only: Some(post_only),
+            ..self
+        }
+    }
+
+    pub fn with_resp_inst(self, resp_inst: ResponseInstruction) -> Self {
+        Self { resp_inst, ..self }
+    }
+}
+
 impl Request for PlaceOrder<'_> {
     type Response = model::PlaceOrderResponse;
 
@@ -313,14 +519,68 @@ impl Request for PlaceOrder<'_> {
     const NEEDS_ACCOUNT_GROUP: bool = true;
     const NEEDS_AUTH: bool = true;
     const API_PATH: &'static str = "/order";
+    const ENDPOINT_CLASS: EndpointClass = EndpointClass::Order;
 
     fn account_type(&self) -> Option<AccountType> {
         Some(self.account_type)
     }
+
+    fn client_id(&self) -> Option<&str> {
+        self.id.as_ref().map(ClientOrderId::as_str)
+    }
+
+    fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.time = timestamp;
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(BitMaxError::InvalidRequest(reason));
+
+        if matches!(self.order_type, model::OrderType::Unknown(_))
+            || matches!(self.side, model::OrderSide::Unknown(_))
+            || matches!(self.time_in_force, model::TimeInForce::Unknown(_))
+        {
+            return invalid(format!(
+                "unknown order type, side or time in force, got {:?} {:?} {:?} order",
+                self.time_in_force, self.order_type, self.side
+            ));
+        }
+        if self.order_type.is_stop() != self.stop_price.is_some() {
+            return invalid(format!(
+                "stop price must be set for stop orders only, got {:?} order with stop price {:?}",
+                self.order_type, self.stop_price
+            ));
+        }
+        if self.order_type.is_limit() != self.order_price.is_some() {
+            return invalid(format!(
+                "price must be set for limit orders only, got {:?} order with price {:?}",
+                self.order_type, self.order_price
+            ));
+        }
+        if self.post_only == Some(true)
+            && (self.order_type != model::OrderType::Limit
+                || self.time_in_force != model::TimeInForce::GTC)
+        {
+            return invalid(format!(
+                "post only is only allowed on GTC limit orders, got {:?} {:?} order",
+                self.time_in_force, self.order_type
+            ));
+        }
+        if self.order_qty <= Fixed9(0) {
+            return invalid(format!("quantity must be positive, got {}", self.order_qty));
+        }
+        for price in self.order_price.iter().chain(&self.stop_price) {
+            if *price <= Fixed9(0) {
+                return invalid(format!("prices must be positive, got {}", price));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[serde_with::skip_serializing_none]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Copy, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct CancelOrder<'a> {
     #[serde(skip)]
@@ -328,7 +588,7 @@ pub struct CancelOrder<'a> {
     pub id: Option<&'a str>,
     pub order_id: &'a str,
     pub symbol: &'a str,
-    pub time: i64,
+    pub time: Timestamp, // set by `BitMaxClient::request`, sent as is by websockets
 }
 
 impl Request for CancelOrder<'_> {
@@ -338,13 +598,120 @@ impl Request for CancelOrder<'_> {
     const NEEDS_ACCOUNT_GROUP: bool = true;
     const NEEDS_AUTH: bool = true;
     const API_PATH: &'static str = "/order";
+    const ENDPOINT_CLASS: EndpointClass = EndpointClass::Order;
+
+    fn account_type(&self) -> Option<AccountType> {
+        Some(self.account_type)
+    }
+
+    fn client_id(&self) -> Option<&str> {
+        self.id
+    }
+
+    fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.time = timestamp;
+    }
+}
+
+/// Place several orders at once, each order gets its own result.
+/// Set the order `id`s to match the results with the orders.
+/// The `time` of every order is set to the exchange time by `BitMaxClient::request`.
+/// The orders must be of the batch account type and use the `Acknowledged` response
+/// instruction, the only one supported for batches.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlaceOrderBatch<'a> {
+    #[serde(skip)]
+    pub account_type: AccountType,
+    pub orders: Vec<PlaceOrder<'a>>,
+}
+
+impl Request for PlaceOrderBatch<'_> {
+    type Response = model::PlaceOrderBatchResponse;
+
+    const METHOD: Method = Method::POST;
+    const NEEDS_ACCOUNT_GROUP: bool = true;
+    const NEEDS_AUTH: bool = true;
+    const API_PATH: &'static str = "/order/batch";
+    const ENDPOINT_CLASS: EndpointClass = EndpointClass::Order;
 
     fn account_type(&self) -> Option<AccountType> {
         Some(self.account_type)
     }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(BitMaxError::InvalidRequest(reason));
+
+        for order in &self.orders {
+            if order.account_type != self.account_type {
+                return invalid(format!(
+                    "{:?} order in a {:?} batch",
+                    order.account_type, self.account_type
+                ));
+            }
+            if !matches!(order.resp_inst, ResponseInstruction::Acknowledged) {
+                return invalid(format!(
+                    "batch orders must be acknowledged, got {:?} response instruction",
+                    order.resp_inst
+                ));
+            }
+            order.validate()?;
+        }
+
+        Ok(())
+    }
+
+    fn set_timestamp(&mut self, timestamp: Timestamp) {
+        for order in &mut self.orders {
+            order.set_timestamp(timestamp);
+        }
+    }
 }
 
+/// Cancel several orders at once, each order gets its own result.
+/// The `time` of every order is set to the exchange time by `BitMaxClient::request`.
+/// The orders must be of the batch account type.
 #[derive(Serialize, Clone, Debug)]
+pub struct CancelOrderBatch<'a> {
+    #[serde(skip)]
+    pub account_type: AccountType,
+    pub orders: Vec<CancelOrder<'a>>,
+}
+
+impl Request for CancelOrderBatch<'_> {
+    type Response = model::CancelOrderBatchResponse;
+
+    const METHOD: Method = Method::DELETE;
+    const NEEDS_ACCOUNT_GROUP: bool = true;
+    const NEEDS_AUTH: bool = true;
+    const API_PATH: &'static str = "/order/batch";
+    const ENDPOINT_CLASS: EndpointClass = EndpointClass::Order;
+
+    fn account_type(&self) -> Option<AccountType> {
+        Some(self.account_type)
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self
+            .orders
+            .iter()
+            .find(|order| order.account_type != self.account_type)
+        {
+            Some(order) => Err(BitMaxError::InvalidRequest(format!(
+                "{:?} order in a {:?} batch",
+                order.account_type, self.account_type
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: Timestamp) {
+        for order in &mut self.orders {
+            order.set_timestamp(timestamp);
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
 pub struct CancelAllOrders<'a> {
     #[serde(skip)]
     pub account_type: AccountType,
@@ -358,6 +725,7 @@ impl Request for CancelAllOrders<'_> {
     const NEEDS_ACCOUNT_GROUP: bool = true;
     const NEEDS_AUTH: bool = true;
     const API_PATH: &'static str = "/order/all";
+    const ENDPOINT_CLASS: EndpointClass = EndpointClass::Order;
 
     fn account_type(&self) -> Option<AccountType> {
         Some(self.account_type)
@@ -406,7 +774,7 @@ impl Request for OrdersStatus<'_> {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Copy, Debug)]
 pub struct OpenOrders {
     #[serde(skip)]
     pub account_type: AccountType,
@@ -457,8 +825,8 @@ pub struct OrderHistory<'a> {
     pub order_type: Option<model::OrderType>,
     pub side: Option<model::OrderSide>,
     pub status: Option<model::OrderStatus>,
-    pub start_time: Option<i64>,
-    pub end_time: Option<i64>,
+    pub start_time: Option<Timestamp>,
+    pub end_time: Option<Timestamp>,
     pub page: Option<u32>,
     pub page_size: Option<u32>,
 }
@@ -471,3 +839,115 @@ impl Request for OrderHistory<'_> {
     const NEEDS_AUTH: bool = true;
     const API_PATH: &'static str = "/order/hist";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{OrderSide, OrderType, TimeInForce};
+
+    fn px(value: &str) -> Fixed9 {
+        value.parse().unwrap()
+    }
+
+    fn limit() -> PlaceOrder<'static> {
+        PlaceOrder::limit("BTC/USDT", OrderSide::Buy, px("0.01"), px("1000"))
+    }
+
+    #[test]
+    fn constructors_are_valid() {
+        let qty = px("0.01");
+
+        assert!(limit().validate().is_ok());
+        assert!(PlaceOrder::market("BTC/USDT", OrderSide::Sell, qty)
+            .validate()
+            .is_ok());
+        assert!(
+            PlaceOrder::stop_limit("BTC/USDT", OrderSide::Buy, qty, px("1000"), px("990"))
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            PlaceOrder::stop_market("BTC/USDT", OrderSide::Sell, qty, px("990"))
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn price_matches_order_type() {
+        let market_with_price = PlaceOrder {
+            order_type: OrderType::Market,
+            ..limit()
+        };
+        let limit_without_price = PlaceOrder {
+            order_price: None,
+            ..limit()
+        };
+
+        assert!(market_with_price.validate().is_err());
+        assert!(limit_without_price.validate().is_err());
+    }
+
+    #[test]
+    fn stop_price_matches_order_type() {
+        let stop_without_stop_price = PlaceOrder {
+            order_type: OrderType::StopLimit,
+            ..limit()
+        };
+        let limit_with_stop_price = PlaceOrder {
+            stop_price: Some(px("990")),
+            ..limit()
+        };
+
+        assert!(stop_without_stop_price.validate().is_err());
+        assert!(limit_with_stop_price.validate().is_err());
+    }
+
+    #[test]
+    fn post_only_needs_gtc_limit() {
+        assert!(limit().with_post_only(true).validate().is_ok());
+        assert!(limit()
+            .with_post_only(true)
+            .with_time_in_force(TimeInForce::IOC)
+            .validate()
+            .is_err());
+        assert!(PlaceOrder::market("BTC/USDT", OrderSide::Buy, px("0.01"))
+            .with_post_only(true)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn amounts_are_positive() {
+        let zero_qty = PlaceOrder {
+            order_qty: Fixed9(0),
+            ..limit()
+        };
+        let negative_price = PlaceOrder {
+            order_price: Some(px("-1")),
+            ..limit()
+        };
+
+        assert!(zero_qty.validate().is_err());
+        assert!(negative_price.validate().is_err());
+    }
+
+    #[test]
+    fn stop_orders_serialize_their_type() {
+        let order = PlaceOrder::stop_market("BTC/USDT", OrderSide::Sell, px("0.01"), px("990"));
+        let order = serde_json::to_value(&order).unwrap();
+
+        assert_eq!(order["orderType"], "stop_market");
+        assert_eq!(order["stopPrice"], "990.000000000");
+    }
+
+    #[test]
+    fn unknown_values_are_rejected() {
+        let unknown_side = PlaceOrder {
+            side: OrderSide::Unknown("short".into()),
+            ..limit()
+        };
+
+        assert!(unknown_side.validate().is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Controls how failed requests are retried.
+///
+/// GET requests are retried automatically. POST and DELETE requests are retried only if
+/// `retry_with_client_id` is set and the request carries a client `id`, so that the exchange
+/// can reject a duplicate instead of placing or cancelling an order twice.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on every following one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Pick a random delay between zero and the backoff value
+    pub jitter: bool,
+    pub retry_with_client_id: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retry_with_client_id: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before the retry number `retry`, starting at 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .checked_mul(1 << retry.min(16))
+            .map_or(self.max_delay, |d| d.min(self.max_delay));
+
+        if self.jitter {
+            backoff.mul_f64(rand::thread_rng().gen::<f64>())
+        } else {
+            backoff
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use futures::{future::BoxFuture, FutureExt};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::error::{BitMaxError, Result};
+
+/// Signs authenticated requests.
+///
+/// Implement it to keep the private key out of the client process, e.g. in a HSM
+/// or a separate signing service. Signing is asynchronous so that such a service can be
+/// reached without blocking the runtime.
+pub trait Signer: Send + Sync + fmt::Debug {
+    /// Returns the raw HMAC-SHA256 signature of `prehash`, the client base64 encodes it
+    fn sign<'a>(&'a self, prehash: &'a [u8]) -> BoxFuture<'a, Result<Vec<u8>>>;
+}
+
+/// Default signer, holds the private key in memory and wipes it on drop
+pub struct HmacSigner {
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl HmacSigner {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self {
+            key: Zeroizing::new(key),
+        }
+    }
+
+    /// `private_key` is the base64 encoded secret, as given by the exchange
+    pub fn from_base64(private_key: &str) -> Result<Self> {
+        base64::decode(private_key)
+            .map(Self::new)
+            .map_err(|e| BitMaxError::InvalidCredentials(e.to_string()))
+    }
+}
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacSigner").finish()
+    }
+}
+
+impl HmacSigner {
+    fn sign_now(&self, prehash: &[u8]) -> Result<Vec<u8>> {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.key)
+            .map_err(|e| BitMaxError::InvalidCredentials(e.to_string()))?;
+        mac.update(prehash);
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign<'a>(&'a self, prehash: &'a [u8]) -> BoxFuture<'a, Result<Vec<u8>>> {
+        futures::future::ready(self.sign_now(prehash)).boxed()
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use futures::{future, pin_mut, TryStreamExt};
+
+use crate::{
+    client::{request, BitMaxClient},
+    error::{BitMaxError, Result},
+    model::{TransactionHistoryEntry, TransactionType},
+};
+
+/// Page size of the transaction history lookups, to limit the requests while a
+/// transaction is not listed yet
+const LOOKUP_PAGE_SIZE: u32 = 50;
+
+impl BitMaxClient {
+    /// Looks up a deposit or withdrawal by the `request_id` returned by the exchange
+    /// in the `tx_type` history of `asset`, fetched page by page until it is found
+    pub async fn transaction(
+        &self,
+        asset: &str,
+        tx_type: TransactionType,
+        request_id: &str,
+    ) -> Result<Option<TransactionHistoryEntry>> {
+        let transactions = self
+            .transaction_history_stream(request::TransactionHistory {
+                asset: Some(asset),
+                tx_type: Some(tx_type),
+                page_size: Some(LOOKUP_PAGE_SIZE),
+                ..Default::default()
+            })
+            .try_filter(|tx| future::ready(tx.request_id == request_id));
+        pin_mut!(transactions);
+
+        transactions.try_next().await
+    }
+
+    /// Polls the transaction history every `poll_interval` until the transaction
+    /// reaches a terminal status, e.g. after a `Withdraw` request.
+    ///
+    /// Returns `BitMaxError::Timeout` if it is still pending, or not found, after `timeout`.
+    pub async fn wait_for_transaction(
+        &self,
+        asset: &str,
+        tx_type: TransactionType,
+        request_id: &str,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<TransactionHistoryEntry> {
+        let poll = async {
+            loop {
+                match self.transaction(asset, tx_type.clone(), request_id).await? {
+                    Some(tx) if tx.status.is_terminal() => return Ok(tx),
+                    _ => tokio::time::delay_for(poll_interval).await,
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, poll).await.map_err(|_| {
+            BitMaxError::Timeout(format!(
+                "transaction {} still pending after {:?}",
+                request_id, timeout
+            ))
+        })?
+    }
+}
@@ -1,13 +1,17 @@
-use failure::Fallible;
 use futures::{
+    ready,
     sink::Sink,
     stream::Stream,
     task::{Context, Poll},
+    Future,
 };
 use log::debug;
 use pin_project::pin_project;
-use std::pin::Pin;
-use tokio::net::TcpStream;
+use std::{collections::VecDeque, pin::Pin, time::Duration};
+use tokio::{
+    net::TcpStream,
+    time::{delay_for, Delay, Instant},
+};
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{http::Request as HttpRequest, protocol::Message as TungsteniteWSMessage},
@@ -16,68 +20,206 @@ use tokio_tungstenite::{
 
 use crate::{
     client::BitMaxClient,
+    error::{BitMaxError, Result},
     model::websocket::{WsInMessage, WsOutMessage},
 };
 
+mod reconnecting;
+mod subscription;
+
+pub use reconnecting::ReconnectingWebsocket;
+pub use subscription::{Subscription, SubscriptionManager};
+
 type WSStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 const WS_ENDPOINT: &str = "/stream";
 
+/// Exchange websocket connection.
+///
+/// Pings of the exchange, application or protocol level, are answered internally. If the
+/// exchange doesn't ping the websocket within the heartbeat timeout, the stream yields `BitMaxError::HeartbeatTimeout` and ends.
 #[pin_project]
 pub struct BitMaxWebsocket {
     #[pin]
     stream: WSStream,
+    // answers to the pings of the exchange, not sent yet
+    pending: VecDeque<TungsteniteWSMessage>,
+    ping_sent: Option<Instant>,
+    latency: Option<Duration>,
+    heartbeat_timeout: Option<Duration>,
+    heartbeat: Option<Delay>,
+    timed_out: bool,
+}
+
+impl BitMaxWebsocket {
+    fn new(stream: WSStream, heartbeat_timeout: Option<Duration>) -> Self {
+        Self {
+            stream,
+            pending: VecDeque::new(),
+            ping_sent: None,
+            latency: None,
+            heartbeat_timeout,
+            heartbeat: heartbeat_timeout.map(delay_for),
+            timed_out: false,
+        }
+    }
+
+    /// Round trip time of the last websocket ping, one is sent along with every answer
+    /// to the pings of the exchange
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// Changes the heartbeat timeout, counting from now. `None` disables it.
+    pub fn set_heartbeat_timeout(&mut self, timeout: Option<Duration>) {
+        self.heartbeat_timeout = timeout;
+        self.heartbeat = timeout.map(delay_for);
+    }
 }
 
 impl BitMaxClient {
-    async fn websocket(&self, auth: bool) -> Fallible<BitMaxWebsocket> {
-        let endpoint = self.render_url("wss", WS_ENDPOINT, auth)?;
+    async fn websocket(&self, auth: bool) -> Result<BitMaxWebsocket> {
+        if auth {
+            self.account_group().await?;
+        }
+
+        let endpoint = self.render_url(&self.ws_url, WS_ENDPOINT, auth)?;
 
         let request = HttpRequest::builder()
             .uri(endpoint)
-            .header("user-agent", "bitmax-rs");
+            .header("user-agent", self.user_agent.as_str());
 
         let request = if auth {
-            self.attach_auth_headers(request, WS_ENDPOINT)?
+            self.attach_auth_headers(request, WS_ENDPOINT, self.server_time().into())
+                .await?
         } else {
             request
         };
 
-        let (stream, _) = connect_async(request.body(())?).await?;
+        let connect = connect_async(request.body(())?);
+        let (stream, _) = match self.ws_connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect).await.map_err(|_| {
+                BitMaxError::Timeout(format!(
+                    "websocket connection not established after {:?}",
+                    timeout
+                ))
+            })??,
+            None => connect.await?,
+        };
 
-        Ok(BitMaxWebsocket { stream })
+        Ok(BitMaxWebsocket::new(stream, self.ws_heartbeat_timeout))
     }
 
-    pub async fn websocket_public(&self) -> Fallible<BitMaxWebsocket> {
+    pub async fn websocket_public(&self) -> Result<BitMaxWebsocket> {
         self.websocket(false).await
     }
 
-    pub async fn websocket_all(&self) -> Fallible<BitMaxWebsocket> {
+    pub async fn websocket_all(&self) -> Result<BitMaxWebsocket> {
         self.websocket(true).await
     }
 }
 
 impl Stream for BitMaxWebsocket {
-    type Item = Fallible<WsInMessage>;
+    type Item = Result<WsInMessage>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let this = self.project();
-        let poll = this.stream.poll_next(cx);
-        poll.map(|msg| msg.map(|msg| msg.map_err(failure::Error::from).and_then(parse_message)))
+        let mut this = self.project();
+        if *this.timed_out {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            send_pending(this.stream.as_mut(), this.pending, this.ping_sent, cx);
+
+            if let Some(heartbeat) = this.heartbeat.as_mut() {
+                if Pin::new(heartbeat).poll(cx).is_ready() {
+                    *this.timed_out = true;
+                    return Poll::Ready(Some(Err(BitMaxError::HeartbeatTimeout)));
+                }
+            }
+
+            let msg = match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                None => return Poll::Ready(None),
+            };
+
+            match msg {
+                // answered by tungstenite
+                TungsteniteWSMessage::Ping(..) => {
+                    reset_heartbeat(this.heartbeat, *this.heartbeat_timeout);
+                    continue;
+                }
+                TungsteniteWSMessage::Pong(..) => {
+                    if let Some(sent) = this.ping_sent.take() {
+                        *this.latency = Some(sent.elapsed());
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            match parse_message(msg) {
+                Ok(WsInMessage::Ping { .. }) => {
+                    let pong = serde_json::to_string(&WsOutMessage::Pong)
+                        .map_err(BitMaxError::Serialize)?;
+                    this.pending.push_back(TungsteniteWSMessage::Text(pong));
+                    this.pending.push_back(TungsteniteWSMessage::Ping(vec![]));
+                    reset_heartbeat(this.heartbeat, *this.heartbeat_timeout);
+                }
+                msg => return Poll::Ready(Some(msg)),
+            }
+        }
     }
 }
 
-fn parse_message(msg: TungsteniteWSMessage) -> Fallible<WsInMessage> {
+/// Restarts the heartbeat timeout, on any ping of the exchange
+fn reset_heartbeat(heartbeat: &mut Option<Delay>, timeout: Option<Duration>) {
+    if let (Some(heartbeat), Some(timeout)) = (heartbeat.as_mut(), timeout) {
+        heartbeat.reset(Instant::now() + timeout);
+    }
+}
+
+/// Sends the queued frames as far as the connection accepts them without waiting,
+/// errors are reported by the next read
+fn send_pending(
+    mut stream: Pin<&mut WSStream>,
+    pending: &mut VecDeque<TungsteniteWSMessage>,
+    ping_sent: &mut Option<Instant>,
+    cx: &mut Context,
+) {
+    while let Some(msg) = pending.pop_front() {
+        match stream.as_mut().poll_ready(cx) {
+            Poll::Ready(Ok(())) => {}
+            _ => {
+                pending.push_front(msg);
+                return;
+            }
+        }
+
+        if let TungsteniteWSMessage::Ping(..) = msg {
+            *ping_sent = Some(Instant::now());
+        }
+        if stream.as_mut().start_send(msg).is_err() {
+            return;
+        }
+    }
+
+    let _ = stream.poll_flush(cx);
+}
+
+fn parse_message(msg: TungsteniteWSMessage) -> Result<WsInMessage> {
     let msg = match msg {
         TungsteniteWSMessage::Text(msg) => msg,
         TungsteniteWSMessage::Binary(_) => {
-            return Err(failure::format_err!("Unexpected binary contents"))
-        }
-        TungsteniteWSMessage::Pong(..) => {
-            return Err(failure::format_err!("Recieved pong in unexpected format"))
+            return Err(BitMaxError::WebsocketProtocol(
+                "Unexpected binary contents".into(),
+            ))
         }
-        TungsteniteWSMessage::Ping(..) => {
-            return Err(failure::format_err!("Recieved ping in unexpected format"))
+        TungsteniteWSMessage::Pong(..) | TungsteniteWSMessage::Ping(..) => {
+            return Err(BitMaxError::WebsocketProtocol(
+                "Unexpected control frame".into(),
+            ))
         }
         TungsteniteWSMessage::Close(..) => {
             return Ok(WsInMessage::Closed);
@@ -86,12 +228,14 @@ fn parse_message(msg: TungsteniteWSMessage) -> Fallible<WsInMessage> {
 
     debug!("Incoming websocket message {}", msg);
 
-    serde_json::from_str(&msg)
-        .map_err(|e| failure::format_err!("could not deserialize {}, error: {:#?}", msg, e))
+    serde_json::from_str(&msg).map_err(|error| BitMaxError::Deserialize {
+        error,
+        payload: msg,
+    })
 }
 
 impl<'a> Sink<WsOutMessage<'a>> for BitMaxWebsocket {
-    type Error = failure::Error;
+    type Error = BitMaxError;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
         let this = self.project();
@@ -99,7 +243,7 @@ impl<'a> Sink<WsOutMessage<'a>> for BitMaxWebsocket {
     }
 
     fn start_send(self: Pin<&mut Self>, msg: WsOutMessage<'a>) -> Result<(), Self::Error> {
-        let msg = serde_json::to_string(&msg)?;
+        let msg = serde_json::to_string(&msg).map_err(BitMaxError::Serialize)?;
         debug!("Sending '{}' through websocket", msg);
         let this = self.project();
         Ok(this.stream.start_send(TungsteniteWSMessage::Text(msg))?)
@@ -0,0 +1,244 @@
+use futures::{
+    channel::mpsc,
+    select,
+    sink::Sink,
+    stream::Stream,
+    task::{Context, Poll},
+    FutureExt, SinkExt, StreamExt,
+};
+use log::{debug, warn};
+use std::pin::Pin;
+use tokio_tungstenite::tungstenite::protocol::Message as TungsteniteWSMessage;
+
+use crate::{
+    client::{websocket::BitMaxWebsocket, BitMaxClient, RetryPolicy},
+    error::{BitMaxError, Result},
+    model::websocket::{WsInMessage, WsOutMessage},
+};
+
+enum Command {
+    Send(String),
+    Subscribe { channel: String, message: String },
+    Unsubscribe { channel: String, message: String },
+}
+
+/// Websocket which reconnects when the connection is lost or the exchange disconnects it.
+///
+/// The active subscriptions are replayed on the new connection, which is then announced by a
+/// `WsInMessage::Reconnected` message. The connection is handled by a background task, which
+/// stops when the websocket is dropped or after `max_attempts` failed reconnection attempts
+/// in a row, in which case the last error is the final item of the stream.
+pub struct ReconnectingWebsocket {
+    commands: mpsc::UnboundedSender<Command>,
+    messages: mpsc::UnboundedReceiver<Result<WsInMessage>>,
+}
+
+impl BitMaxClient {
+    pub async fn reconnecting_websocket_public(
+        &self,
+        policy: RetryPolicy,
+    ) -> Result<ReconnectingWebsocket> {
+        self.reconnecting_websocket(false, policy).await
+    }
+
+    pub async fn reconnecting_websocket_all(
+        &self,
+        policy: RetryPolicy,
+    ) -> Result<ReconnectingWebsocket> {
+        self.reconnecting_websocket(true, policy).await
+    }
+
+    async fn reconnecting_websocket(
+        &self,
+        auth: bool,
+        policy: RetryPolicy,
+    ) -> Result<ReconnectingWebsocket> {
+        let ws = self.websocket(auth).await?;
+        let (commands, commands_rx) = mpsc::unbounded();
+        let (messages_tx, messages) = mpsc::unbounded();
+
+        let task = Connection {
+            client: self.clone(),
+            auth,
+            policy,
+            subscriptions: vec![],
+            messages: messages_tx,
+        };
+        tokio::spawn(task.run(ws, commands_rx));
+
+        Ok(ReconnectingWebsocket { commands, messages })
+    }
+}
+
+struct Connection {
+    client: BitMaxClient,
+    auth: bool,
+    policy: RetryPolicy,
+    // active subscriptions, as channel and subscription message
+    subscriptions: Vec<(String, String)>,
+    messages: mpsc::UnboundedSender<Result<WsInMessage>>,
+}
+
+impl Connection {
+    async fn run(
+        mut self,
+        mut ws: BitMaxWebsocket,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+    ) {
+        loop {
+            let lost = select! {
+                command = commands.next() => match command {
+                    Some(command) => self.handle_command(&mut ws, command).await,
+                    None => {
+                        let _ = ws.close().await;
+                        return;
+                    }
+                },
+                message = ws.next().fuse() => match message {
+                    Some(Ok(WsInMessage::Closed)) | None => true,
+                    Some(Ok(message @ WsInMessage::Disconnected { .. })) => {
+                        warn!("Websocket disconnected by the exchange: {:?}", message);
+                        let _ = self.messages.unbounded_send(Ok(message));
+                        true
+                    }
+                    Some(Err(e @ BitMaxError::Websocket(_)))
+                    | Some(Err(e @ BitMaxError::HeartbeatTimeout)) => {
+                        warn!("Websocket connection lost: {}", e);
+                        true
+                    }
+                    Some(message) => {
+                        if self.messages.unbounded_send(message).is_err() {
+                            return;
+                        }
+                        false
+                    }
+                },
+            };
+
+            if lost {
+                if self.messages.is_closed() {
+                    return;
+                }
+                ws = match self.reconnect().await {
+                    Ok(ws) => ws,
+                    Err(e) => {
+                        let _ = self.messages.unbounded_send(Err(e));
+                        return;
+                    }
+                };
+                if self
+                    .messages
+                    .unbounded_send(Ok(WsInMessage::Reconnected))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns whether the connection was lost
+    async fn handle_command(&mut self, ws: &mut BitMaxWebsocket, command: Command) -> bool {
+        let message = match command {
+            Command::Send(message) => message,
+            Command::Subscribe { channel, message } => {
+                self.subscriptions.retain(|(ch, _)| *ch != channel);
+                self.subscriptions.push((channel, message.clone()));
+                message
+            }
+            Command::Unsubscribe { channel, message } => {
+                self.subscriptions.retain(|(ch, _)| *ch != channel);
+                message
+            }
+        };
+
+        // a message failing to be sent is lost, apart from the subscriptions replayed
+        // after reconnecting
+        ws.stream
+            .send(TungsteniteWSMessage::Text(message))
+            .await
+            .is_err()
+    }
+
+    async fn reconnect(&self) -> Result<BitMaxWebsocket> {
+        let mut attempt = 0;
+        loop {
+            tokio::time::delay_for(self.policy.delay(attempt)).await;
+
+            match self.connect().await {
+                Ok(ws) => return Ok(ws),
+                Err(e) if attempt + 1 < self.policy.max_attempts => {
+                    warn!(
+                        "Websocket reconnection attempt {} failed: {}",
+                        attempt + 1,
+                        e
+                    );
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<BitMaxWebsocket> {
+        let mut ws = self.client.websocket(self.auth).await?;
+        for (channel, message) in &self.subscriptions {
+            debug!("Resubscribing to {}", channel);
+            ws.stream
+                .send(TungsteniteWSMessage::Text(message.clone()))
+                .await?;
+        }
+
+        Ok(ws)
+    }
+}
+
+impl Stream for ReconnectingWebsocket {
+    type Item = Result<WsInMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.messages.poll_next_unpin(cx)
+    }
+}
+
+impl<'a> Sink<WsOutMessage<'a>> for ReconnectingWebsocket {
+    type Error = BitMaxError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.commands.poll_ready(cx).map_err(task_stopped)
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: WsOutMessage<'a>) -> Result<(), Self::Error> {
+        let message = serde_json::to_string(&msg).map_err(BitMaxError::Serialize)?;
+        debug!("Sending '{}' through websocket", message);
+
+        let command = match msg {
+            WsOutMessage::Subscribe { ch, .. } => Command::Subscribe {
+                channel: ch.channel(),
+                message,
+            },
+            WsOutMessage::Unsubscribe { ch, .. } => Command::Unsubscribe {
+                channel: ch.channel(),
+                message,
+            },
+            _ => Command::Send(message),
+        };
+
+        self.commands
+            .unbounded_send(command)
+            .map_err(|e| task_stopped(e.into_send_error()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.commands.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn task_stopped(_: mpsc::SendError) -> BitMaxError {
+    BitMaxError::WebsocketProtocol("the websocket connection was given up".into())
+}
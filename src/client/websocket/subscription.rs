@@ -0,0 +1,458 @@
+use futures::{
+    channel::{mpsc, oneshot},
+    select,
+    sink::Sink,
+    stream::Stream,
+    task::{Context, Poll},
+    FutureExt, SinkExt, StreamExt,
+};
+use log::{debug, warn};
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    client::InstrumentRegistry,
+    error::{ApiError, BitMaxError, Result},
+    model::{
+        websocket::{
+            BarData, BboData, DepthData, RefPxData, SubscribeTopic, Trade, WsInMessage,
+            WsOutMessage,
+        },
+        Interval,
+    },
+};
+
+/// Owned `SubscribeTopic`
+#[derive(Debug, Clone)]
+enum Topic {
+    Depth(String),
+    Bbo(String),
+    Trades(String),
+    Bar(String, Interval),
+    RefPx(String),
+}
+
+impl Topic {
+    fn symbol(&self) -> &str {
+        match self {
+            Self::Depth(symbol)
+            | Self::Bbo(symbol)
+            | Self::Trades(symbol)
+            | Self::Bar(symbol, _)
+            | Self::RefPx(symbol) => symbol,
+        }
+    }
+
+    fn as_subscribe_topic(&self) -> SubscribeTopic<'_> {
+        match self {
+            Self::Depth(symbol) => SubscribeTopic::Depth { symbol },
+            Self::Bbo(symbol) => SubscribeTopic::Bbo { symbol },
+            Self::Trades(symbol) => SubscribeTopic::Trades { symbol },
+            Self::Bar(symbol, interval) => SubscribeTopic::Bar {
+                symbol,
+                interval: interval.clone(),
+            },
+            Self::RefPx(symbol) => SubscribeTopic::RefPx { symbol },
+        }
+    }
+}
+
+enum Payload {
+    Depth(DepthData),
+    Bbo(BboData),
+    Trades(Vec<Trade>),
+    Bar(BarData),
+    RefPx(RefPxData),
+}
+
+enum Subscriber {
+    Depth(mpsc::UnboundedSender<DepthData>),
+    Bbo(mpsc::UnboundedSender<BboData>),
+    Trades(mpsc::UnboundedSender<Trade>),
+    Bar(mpsc::UnboundedSender<BarData>),
+    RefPx(mpsc::UnboundedSender<RefPxData>),
+}
+
+impl Subscriber {
+    /// Returns false once the subscription stream is dropped
+    fn send(&self, payload: &Payload) -> bool {
+        match (self, payload) {
+            (Self::Depth(tx), Payload::Depth(data)) => tx.unbounded_send(data.clone()).is_ok(),
+            (Self::Bbo(tx), Payload::Bbo(data)) => tx.unbounded_send(data.clone()).is_ok(),
+            (Self::Trades(tx), Payload::Trades(trades)) => trades
+                .iter()
+                .all(|trade| tx.unbounded_send(trade.clone()).is_ok()),
+            (Self::Bar(tx), Payload::Bar(data)) => tx.unbounded_send(data.clone()).is_ok(),
+            (Self::RefPx(tx), Payload::RefPx(data)) => tx.unbounded_send(data.clone()).is_ok(),
+            _ => true,
+        }
+    }
+}
+
+enum Command {
+    Subscribe {
+        topic: Topic,
+        id: u64,
+        subscriber: Subscriber,
+        confirmation: oneshot::Sender<Result<()>>,
+    },
+    Unsubscribe {
+        channel: String,
+        id: u64,
+    },
+}
+
+/// Stream of the messages of one channel, the channel is unsubscribed from once
+/// all its subscriptions are dropped
+pub struct Subscription<T> {
+    channel: String,
+    id: u64,
+    messages: mpsc::UnboundedReceiver<T>,
+    confirmation: Option<oneshot::Receiver<Result<()>>>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl<T> Subscription<T> {
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Waits for the exchange to confirm the subscription, returns immediately if it was
+    /// already confirmed
+    pub async fn confirmed(&mut self) -> Result<()> {
+        match self.confirmation.take() {
+            Some(confirmation) => confirmation.await.unwrap_or_else(|_| {
+                Err(BitMaxError::WebsocketProtocol(
+                    "websocket closed before the subscription was confirmed".into(),
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.messages.poll_next_unpin(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        let _ = self.commands.unbounded_send(Command::Unsubscribe {
+            channel: std::mem::take(&mut self.channel),
+            id: self.id,
+        });
+    }
+}
+
+/// Splits the market data of a websocket into a typed stream per subscription.
+///
+/// Subscriptions to the same channel share a single exchange subscription, which lasts until
+/// the last of them is dropped. Messages that don't belong to a subscription, e.g. order
+/// updates or errors, are yielded by the stream returned along with the manager.
+///
+/// The websocket is handled by a background task, which stops once the websocket ends or the
+/// manager and all its subscriptions are dropped.
+pub struct SubscriptionManager {
+    commands: mpsc::UnboundedSender<Command>,
+    next_id: AtomicU64,
+    registry: Option<InstrumentRegistry>,
+}
+
+impl SubscriptionManager {
+    pub fn new<W>(ws: W) -> (Self, impl Stream<Item = Result<WsInMessage>>)
+    where
+        W: Stream<Item = Result<WsInMessage>>
+            + for<'a> Sink<WsOutMessage<'a>, Error = BitMaxError>
+            + Unpin
+            + Send
+            + 'static,
+    {
+        let (commands, commands_rx) = mpsc::unbounded();
+        let (unrouted_tx, unrouted) = mpsc::unbounded();
+
+        let router = Router {
+            topics: HashMap::new(),
+            unrouted: unrouted_tx,
+        };
+        tokio::spawn(router.run(ws, commands_rx));
+
+        let manager = Self {
+            commands,
+            next_id: AtomicU64::new(0),
+            registry: None,
+        };
+        (manager, unrouted)
+    }
+
+    /// Rejects subscriptions to symbols that are not listed in `registry`, instead of
+    /// waiting for the exchange to refuse them
+    pub fn with_registry(self, registry: InstrumentRegistry) -> Self {
+        Self {
+            registry: Some(registry),
+            ..self
+        }
+    }
+
+    pub fn subscribe_depth(&self, symbol: &str) -> Result<Subscription<DepthData>> {
+        self.subscribe(Topic::Depth(symbol.into()), Subscriber::Depth)
+    }
+
+    pub fn subscribe_bbo(&self, symbol: &str) -> Result<Subscription<BboData>> {
+        self.subscribe(Topic::Bbo(symbol.into()), Subscriber::Bbo)
+    }
+
+    /// Trades are yielded one by one, even when the exchange batches them
+    pub fn subscribe_trades(&self, symbol: &str) -> Result<Subscription<Trade>> {
+        self.subscribe(Topic::Trades(symbol.into()), Subscriber::Trades)
+    }
+
+    pub fn subscribe_bar(&self, symbol: &str, interval: Interval) -> Result<Subscription<BarData>> {
+        self.subscribe(Topic::Bar(symbol.into(), interval), Subscriber::Bar)
+    }
+
+    pub fn subscribe_ref_px(&self, symbol: &str) -> Result<Subscription<RefPxData>> {
+        self.subscribe(Topic::RefPx(symbol.into()), Subscriber::RefPx)
+    }
+
+    fn subscribe<T>(
+        &self,
+        topic: Topic,
+        subscriber: fn(mpsc::UnboundedSender<T>) -> Subscriber,
+    ) -> Result<Subscription<T>> {
+        if let Some(registry) = &self.registry {
+            registry.snapshot().rules().product(topic.symbol())?;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let channel = topic.as_subscribe_topic().channel();
+        let (tx, messages) = mpsc::unbounded();
+        let (confirmation_tx, confirmation) = oneshot::channel();
+
+        self.commands
+            .unbounded_send(Command::Subscribe {
+                topic,
+                id,
+                subscriber: subscriber(tx),
+                confirmation: confirmation_tx,
+            })
+            .map_err(|_| BitMaxError::WebsocketProtocol("the websocket is closed".into()))?;
+
+        Ok(Subscription {
+            channel,
+            id,
+            messages,
+            confirmation: Some(confirmation),
+            commands: self.commands.clone(),
+        })
+    }
+}
+
+struct TopicState {
+    topic: Topic,
+    subscribers: Vec<(u64, Subscriber)>,
+    confirmed: bool,
+    // subscriptions waiting for the exchange to confirm the channel
+    waiting: Vec<oneshot::Sender<Result<()>>>,
+}
+
+struct Router {
+    // by channel
+    topics: HashMap<String, TopicState>,
+    unrouted: mpsc::UnboundedSender<Result<WsInMessage>>,
+}
+
+impl Router {
+    async fn run<W>(mut self, mut ws: W, mut commands: mpsc::UnboundedReceiver<Command>)
+    where
+        W: Stream<Item = Result<WsInMessage>>
+            + for<'a> Sink<WsOutMessage<'a>, Error = BitMaxError>
+            + Unpin,
+    {
+        loop {
+            select! {
+                command = commands.next() => match command {
+                    Some(command) => {
+                        if let Err(e) = self.handle_command(&mut ws, command).await {
+                            let _ = self.unrouted.unbounded_send(Err(e));
+                        }
+                    }
+                    None => {
+                        let _ = ws.close().await;
+                        return;
+                    }
+                },
+                message = ws.next().fuse() => match message {
+                    Some(Ok(message)) => self.route(&mut ws, message).await,
+                    Some(Err(e)) => {
+                        let _ = self.unrouted.unbounded_send(Err(e));
+                    }
+                    None => return,
+                },
+            }
+        }
+    }
+
+    async fn handle_command<W>(&mut self, ws: &mut W, command: Command) -> Result<()>
+    where
+        W: for<'a> Sink<WsOutMessage<'a>, Error = BitMaxError> + Unpin,
+    {
+        match command {
+            Command::Subscribe {
+                topic,
+                id,
+                subscriber,
+                confirmation,
+            } => {
+                let channel = topic.as_subscribe_topic().channel();
+                let state = self
+                    .topics
+                    .entry(channel.clone())
+                    .or_insert_with(|| TopicState {
+                        topic,
+                        subscribers: vec![],
+                        confirmed: false,
+                        waiting: vec![],
+                    });
+
+                state.subscribers.push((id, subscriber));
+                if state.confirmed {
+                    let _ = confirmation.send(Ok(()));
+                } else {
+                    state.waiting.push(confirmation);
+                }
+
+                if state.subscribers.len() == 1 {
+                    debug!("Subscribing to {}", channel);
+                    ws.send(WsOutMessage::Subscribe {
+                        ch: state.topic.as_subscribe_topic(),
+                        id: None,
+                    })
+                    .await?;
+                }
+            }
+            Command::Unsubscribe { channel, id } => {
+                if let Some(state) = self.topics.get_mut(&channel) {
+                    state.subscribers.retain(|(i, _)| *i != id);
+                    self.unsubscribe_unused(ws, &channel).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn route<W>(&mut self, ws: &mut W, message: WsInMessage)
+    where
+        W: for<'a> Sink<WsOutMessage<'a>, Error = BitMaxError> + Unpin,
+    {
+        let (channel, payload) = match message {
+            WsInMessage::Subscribed { code, ch, .. } => {
+                self.confirm(&ch, code);
+                return;
+            }
+            WsInMessage::Unsubscribed { code, ch, .. } => {
+                if code != 0 {
+                    warn!("Failed to unsubscribe from {}: code {}", ch, code);
+                }
+                return;
+            }
+            WsInMessage::Reconnected => {
+                // the subscriptions are replayed, the exchange confirms them again
+                for state in self.topics.values_mut() {
+                    state.confirmed = false;
+                }
+                let _ = self.unrouted.unbounded_send(Ok(WsInMessage::Reconnected));
+                return;
+            }
+            WsInMessage::Depth { symbol, data } => (
+                SubscribeTopic::Depth { symbol: &symbol }.channel(),
+                Payload::Depth(data),
+            ),
+            WsInMessage::Bbo { symbol, data } => (
+                SubscribeTopic::Bbo { symbol: &symbol }.channel(),
+                Payload::Bbo(data),
+            ),
+            WsInMessage::Trades { symbol, data } => (
+                SubscribeTopic::Trades { symbol: &symbol }.channel(),
+                Payload::Trades(data),
+            ),
+            WsInMessage::Bar { symbol, data } => (
+                SubscribeTopic::Bar {
+                    symbol: &symbol,
+                    interval: data.interval.clone(),
+                }
+                .channel(),
+                Payload::Bar(data),
+            ),
+            WsInMessage::RefPx { symbol, data } => (
+                SubscribeTopic::RefPx { symbol: &symbol }.channel(),
+                Payload::RefPx(data),
+            ),
+            message => {
+                let _ = self.unrouted.unbounded_send(Ok(message));
+                return;
+            }
+        };
+
+        if let Some(state) = self.topics.get_mut(&channel) {
+            state.subscribers.retain(|(_, s)| s.send(&payload));
+            if let Err(e) = self.unsubscribe_unused(ws, &channel).await {
+                let _ = self.unrouted.unbounded_send(Err(e));
+            }
+        }
+    }
+
+    fn confirm(&mut self, channel: &str, code: u32) {
+        let state = match self.topics.get_mut(channel) {
+            Some(state) => state,
+            None => return,
+        };
+
+        if code == 0 {
+            state.confirmed = true;
+            for confirmation in state.waiting.drain(..) {
+                let _ = confirmation.send(Ok(()));
+            }
+            return;
+        }
+
+        // the subscription streams end
+        let state = self.topics.remove(channel).unwrap();
+        for confirmation in state.waiting {
+            let _ = confirmation.send(Err(BitMaxError::Api(ApiError {
+                code: (code as i64).into(),
+                reason: String::new(),
+                message: format!("subscription to {} rejected", channel),
+                info: None,
+            })));
+        }
+    }
+
+    async fn unsubscribe_unused<W>(&mut self, ws: &mut W, channel: &str) -> Result<()>
+    where
+        W: for<'a> Sink<WsOutMessage<'a>, Error = BitMaxError> + Unpin,
+    {
+        if !self
+            .topics
+            .get(channel)
+            .is_some_and(|state| state.subscribers.is_empty())
+        {
+            return Ok(());
+        }
+
+        let state = self.topics.remove(channel).unwrap();
+        debug!("Unsubscribing from {}", channel);
+        ws.send(WsOutMessage::Unsubscribe {
+            ch: state.topic.as_subscribe_topic(),
+            id: None,
+        })
+        .await
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio_tungstenite::tungstenite;
+
+pub type Result<T, E = BitMaxError> = std::result::Result<T, E>;
+
+/// Error type returned by every fallible operation of the crate
+#[derive(Debug)]
+pub enum BitMaxError {
+    /// The HTTP request could not be sent or its response could not be read
+    Transport(reqwest::Error),
+    /// The request url could not be built
+    Url(url::ParseError),
+    /// The exchange replied with a non-success HTTP status
+    HttpStatus { status: StatusCode, body: String },
+    /// The exchange replied with a non zero response code
+    Api(ApiError),
+    /// The request could not be serialized
+    Serialize(serde_json::Error),
+    /// The exchange response could not be deserialized, `payload` holds the raw message
+    Deserialize {
+        error: serde_json::Error,
+        payload: String,
+    },
+    /// The request needs authentication, but the client has no keys
+    MissingAuth,
+    /// The request needs an account group, but the client doesn't have one
+    MissingAccountGroup,
+    /// The provided api keys are malformed or could not be loaded
+    InvalidCredentials(String),
+    /// A file could not be read
+    Io(std::io::Error),
+    /// The request was rejected locally, before being sent to the exchange
+    InvalidRequest(String),
+    /// A string could not be parsed into a `Fixed9` value
+    ParseFixed9(String),
+    /// The result of a `Fixed9` operation doesn't fit in a `Fixed9` value
+    Fixed9Overflow(String),
+    /// The websocket connection failed
+    Websocket(tungstenite::Error),
+    /// The websocket received a frame it can't handle
+    WebsocketProtocol(String),
+    /// The exchange stopped pinging the websocket, the connection is likely dead
+    HeartbeatTimeout,
+    /// Depth updates were missed, the order book needs a new snapshot
+    SequenceGap { expected: u64, received: u64 },
+    /// An operation did not complete in the allotted time
+    Timeout(String),
+}
+
+impl fmt::Display for BitMaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::Url(e) => write!(f, "invalid url: {}", e),
+            Self::HttpStatus { status, body } => {
+                write!(f, "http error: {}; body: {};", status, body)
+            }
+            Self::Api(e) => write!(f, "{}", e),
+            Self::Serialize(e) => write!(f, "could not serialize request: {}", e),
+            Self::Deserialize { error, payload } => {
+                write!(f, "error {} while deserializing {}", error, payload)
+            }
+            Self::MissingAuth => write!(f, "missing auth keys"),
+            Self::MissingAccountGroup => write!(f, "missing account group"),
+            Self::InvalidCredentials(reason) => write!(f, "invalid credentials: {}", reason),
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::InvalidRequest(reason) => write!(f, "invalid request: {}", reason),
+            Self::ParseFixed9(reason) => write!(f, "couldn't parse fixed9 value: {}", reason),
+            Self::Fixed9Overflow(reason) => write!(f, "fixed9 overflow: {}", reason),
+            Self::Websocket(e) => write!(f, "websocket error: {}", e),
+            Self::WebsocketProtocol(reason) => write!(f, "websocket protocol error: {}", reason),
+            Self::HeartbeatTimeout => write!(f, "no heartbeat received from the websocket"),
+            Self::SequenceGap { expected, received } => write!(
+                f,
+                "sequence gap: expected seqnum {}, received {}",
+                expected, received
+            ),
+            Self::Timeout(reason) => write!(f, "timed out: {}", reason),
+        }
+    }
+}
+
+impl BitMaxError {
+    /// The request may succeed if it is sent again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            Self::HttpStatus { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            Self::Api(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for BitMaxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::Url(e) => Some(e),
+            Self::Api(e) => Some(e),
+            Self::Serialize(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Deserialize { error, .. } => Some(error),
+            Self::Websocket(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApiError> for BitMaxError {
+    fn from(e: ApiError) -> Self {
+        Self::Api(e)
+    }
+}
+
+impl From<reqwest::Error> for BitMaxError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl From<std::io::Error> for BitMaxError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<url::ParseError> for BitMaxError {
+    fn from(e: url::ParseError) -> Self {
+        Self::Url(e)
+    }
+}
+
+impl From<tungstenite::Error> for BitMaxError {
+    fn from(e: tungstenite::Error) -> Self {
+        Self::Websocket(e)
+    }
+}
+
+impl From<tungstenite::http::Error> for BitMaxError {
+    fn from(e: tungstenite::http::Error) -> Self {
+        Self::Websocket(e.into())
+    }
+}
+
+/// Error reported by the exchange in a response with a non zero code
+#[derive(Deserialize, Clone, Debug)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub info: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    /// The request may succeed if it is sent again later
+    pub fn is_retryable(&self) -> bool {
+        self.code.is_retryable()
+    }
+
+    /// The request was rejected because of bad keys, signature or permissions
+    pub fn is_auth_failure(&self) -> bool {
+        self.code.is_auth_failure()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "non zero response code {} ({}): {}",
+            self.code.value(),
+            self.reason,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Error codes documented at https://bitmax-exchange.github.io/bitmax-pro-api/#error-code
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(from = "i64")]
+pub enum ApiErrorCode {
+    InvalidHttpInput,
+    DataNotAvailable,
+    KeyConflict,
+    InvalidRequestData,
+    InvalidWsRequestData,
+    InvalidArgument,
+    EncryptionError, // invalid signature
+    SymbolError,
+    AuthorizationNeeded,
+    InvalidOperation,
+    InvalidTimestamp,
+    InvalidStrFormat,
+    InvalidNumFormat,
+    UnknownError,
+    InvalidJsonFormat,
+    AuthenticationFailed,
+    TooManyAttempts, // too many requests
+    AccountNotFound,
+    AccountNotSetup,
+    AccountAlreadyExist,
+    AccountError,
+    CodeNotFound,
+    CodeExpired,
+    CodeMismatch,
+    PasswordError,
+    CodeGenFailed,
+    FakeCokeVerify,
+    SecurityAlert,
+    RestrictedAccount,
+    PermissionDenied,
+    InvalidPrice,
+    InvalidQty,
+    InvalidSide,
+    InvalidNotional,
+    InvalidType,
+    InvalidOrderId, // order not found
+    InvalidTimeInForce,
+    InvalidOrderParameter,
+    TradingViolation,
+    InvalidBalance, // insufficient balance
+    InvalidProduct, // invalid symbol
+    InvalidBatchOrder,
+    TradingRestricted,
+    TradingDisabled,
+    NoMarketPrice,
+    InvalidMarginBalance,
+    InvalidMarginAccount,
+    MarginTooRisky,
+    InvalidMarginAsset,
+    InvalidReferencePrice,
+    ServerError,
+    HumanChallenge,
+    Unknown(i64),
+}
+
+impl ApiErrorCode {
+    const CODES: &'static [(i64, ApiErrorCode)] = &[
+        (100001, Self::InvalidHttpInput),
+        (100002, Self::DataNotAvailable),
+        (100003, Self::KeyConflict),
+        (100004, Self::InvalidRequestData),
+        (100005, Self::InvalidWsRequestData),
+        (100006, Self::InvalidArgument),
+        (100007, Self::EncryptionError),
+        (100008, Self::SymbolError),
+        (100009, Self::AuthorizationNeeded),
+        (100010, Self::InvalidOperation),
+        (100011, Self::InvalidTimestamp),
+        (100012, Self::InvalidStrFormat),
+        (100013, Self::InvalidNumFormat),
+        (100101, Self::UnknownError),
+        (150001, Self::InvalidJsonFormat),
+        (200001, Self::AuthenticationFailed),
+        (200002, Self::TooManyAttempts),
+        (200003, Self::AccountNotFound),
+        (200004, Self::AccountNotSetup),
+        (200005, Self::AccountAlreadyExist),
+        (200006, Self::AccountError),
+        (200007, Self::CodeNotFound),
+        (200008, Self::CodeExpired),
+        (200009, Self::CodeMismatch),
+        (200010, Self::PasswordError),
+        (200011, Self::CodeGenFailed),
+        (200012, Self::FakeCokeVerify),
+        (200013, Self::SecurityAlert),
+        (200014, Self::RestrictedAccount),
+        (200015, Self::PermissionDenied),
+        (300001, Self::InvalidPrice),
+        (300002, Self::InvalidQty),
+        (300003, Self::InvalidSide),
+        (300004, Self::InvalidNotional),
+        (300005, Self::InvalidType),
+        (300006, Self::InvalidOrderId),
+        (300007, Self::InvalidTimeInForce),
+        (300008, Self::InvalidOrderParameter),
+        (300009, Self::TradingViolation),
+        (300011, Self::InvalidBalance),
+        (300012, Self::InvalidProduct),
+        (300013, Self::InvalidBatchOrder),
+        (300020, Self::TradingRestricted),
+        (300021, Self::TradingDisabled),
+        (300031, Self::NoMarketPrice),
+        (310001, Self::InvalidMarginBalance),
+        (310002, Self::InvalidMarginAccount),
+        (310003, Self::MarginTooRisky),
+        (310004, Self::InvalidMarginAsset),
+        (310005, Self::InvalidReferencePrice),
+        (510001, Self::ServerError),
+        (900001, Self::HumanChallenge),
+    ];
+
+    /// Numeric code as sent by the exchange
+    pub fn value(self) -> i64 {
+        match self {
+            Self::Unknown(code) => code,
+            known => Self::CODES
+                .iter()
+                .find(|(_, c)| *c == known)
+                .map(|(code, _)| *code)
+                .unwrap(), // every known variant is listed in `CODES`
+        }
+    }
+
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::TooManyAttempts | Self::ServerError | Self::UnknownError | Self::NoMarketPrice
+        )
+    }
+
+    pub fn is_auth_failure(self) -> bool {
+        matches!(
+            self,
+            Self::EncryptionError
+                | Self::AuthorizationNeeded
+                | Self::InvalidTimestamp
+                | Self::AuthenticationFailed
+                | Self::PermissionDenied
+                | Self::RestrictedAccount
+        )
+    }
+}
+
+impl From<i64> for ApiErrorCode {
+    fn from(code: i64) -> Self {
+        Self::CODES
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, known)| *known)
+            .unwrap_or(Self::Unknown(code))
+    }
+}
@@ -1,7 +1,15 @@
 #![warn(clippy::all)]
 
 mod client;
+mod error;
 pub mod model;
 
-pub use client::{request, websocket::BitMaxWebsocket, BitMaxClient};
-pub use model::Fixed9;
+pub use client::{
+    request,
+    websocket::{BitMaxWebsocket, ReconnectingWebsocket, Subscription, SubscriptionManager},
+    AssetBalance, BarSeries, BarhistRange, BitMaxClient, BitMaxClientBuilder, Budget, Credentials,
+    EndpointClass, Environment, HmacSigner, InstrumentRegistry, Instruments, OrderBookFeed, Quota,
+    RateLimiter, RetryPolicy, Signer,
+};
+pub use error::{ApiError, ApiErrorCode, BitMaxError, Result};
+pub use model::{ClientOrderId, Fixed9, Timestamp};
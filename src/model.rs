@@ -1,14 +1,22 @@
-use std::fmt;
+use std::{borrow::Cow, collections::HashMap, fmt};
 
 use serde::{
     de::{self, IntoDeserializer},
     Deserialize, Deserializer, Serialize,
 };
 
+mod client_order_id;
 mod fixed9;
+mod order_book;
+mod rules;
+mod timestamp;
 pub mod websocket;
 
-pub use fixed9::Fixed9;
+pub use client_order_id::ClientOrderId;
+pub use fixed9::{Fixed9, RoundingMode};
+pub use order_book::OrderBook;
+pub use rules::ProductRules;
+pub use timestamp::Timestamp;
 
 fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
 where
@@ -23,13 +31,66 @@ where
     }
 }
 
+/// Defines an enum (de)serialized from a string. Values unknown to this crate, e.g. added
+/// to the exchange after its release, are kept in the `Unknown` variant instead of failing.
+macro_rules! string_enum {
+    (
+        pub enum $name:ident {
+            $($variant:ident => $value:literal $(| $alias:literal)*,)*
+        }
+    ) => {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $value,)*
+                    Self::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($value $(| $alias)* => Self::$variant,)*
+                    value => Self::Unknown(value.into()),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = Cow::<str>::deserialize(deserializer)?;
+                Ok(Self::from(value.as_ref()))
+            }
+        }
+    };
+}
+
 fn de_f64_str<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
     let s: &str = Deserialize::deserialize(deserializer)?;
 
     s.parse::<f64>().map_err(de::Error::custom)
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum AccountType {
     #[serde(alias = "CASH")]
@@ -38,6 +99,7 @@ pub enum AccountType {
     Margin,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for AccountType {
     fn default() -> Self {
         AccountType::Cash
@@ -47,12 +109,13 @@ impl Default for AccountType {
 // Price/qty pair
 pub type PriceQty = (Fixed9, Fixed9);
 
-#[derive(Clone, Copy, Debug, Deserialize)]
-pub enum AssetStatus {
-    Normal,
-    NoDeposit,
-    NoWithdraw,
-    NoTransaction,
+string_enum! {
+    pub enum AssetStatus {
+        Normal => "Normal",
+        NoDeposit => "NoDeposit",
+        NoWithdraw => "NoWithdraw",
+        NoTransaction => "NoTransaction",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -60,20 +123,19 @@ pub enum AssetStatus {
 pub struct Asset {
     pub asset_code: String,
     pub asset_name: String,
-    #[serde(deserialize_with = "de_f64_str")]
-    pub min_withdrawal_amt: f64,
-    #[serde(deserialize_with = "de_f64_str")]
-    pub withdrawal_fee: f64,
+    pub min_withdrawal_amt: Fixed9,
+    pub withdrawal_fee: Fixed9,
     pub precision_scale: u32,
     pub native_scale: u32,
     pub status: AssetStatus,
 }
 
-#[derive(Deserialize, Clone, Debug, Copy)]
-pub enum ComissionType {
-    Base,
-    Quote,
-    Received,
+string_enum! {
+    pub enum ComissionType {
+        Base => "Base",
+        Quote => "Quote",
+        Received => "Received",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -91,11 +153,11 @@ pub struct Product {
     pub commission_reserve_rate: Fixed9,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum SymbolType {
-    Spot,
-    Derivatives,
+string_enum! {
+    pub enum SymbolType {
+        Spot => "spot",
+        Derivatives => "derivatives",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -113,54 +175,20 @@ pub struct Ticker {
     pub type_: SymbolType,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
-pub enum Interval {
-    #[serde(rename = "1")]
-    T1m,
-    #[serde(rename = "5")]
-    T5m,
-    #[serde(rename = "15")]
-    T15m,
-    #[serde(rename = "30")]
-    T30m,
-    #[serde(rename = "60")]
-    T60m,
-    #[serde(rename = "120")]
-    T120m,
-    #[serde(rename = "240")]
-    T240m,
-    #[serde(rename = "360")]
-    T360m,
-    #[serde(rename = "720")]
-    T720m,
-    #[serde(rename = "1d")]
-    T1d,
-    #[serde(rename = "1w")]
-    T1w,
-    #[serde(rename = "1m")]
-    T1M,
-}
-
-impl fmt::Display for Interval {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::T1m => "1",
-                Self::T5m => "5",
-                Self::T15m => "15",
-                Self::T30m => "30",
-                Self::T60m => "60",
-                Self::T120m => "120",
-                Self::T240m => "240",
-                Self::T360m => "360",
-                Self::T720m => "720",
-                Self::T1d => "1d",
-                Self::T1w => "1w",
-                Self::T1M => "1m",
-            }
-        )
+string_enum! {
+    pub enum Interval {
+        T1m => "1",
+        T5m => "5",
+        T15m => "15",
+        T30m => "30",
+        T60m => "60",
+        T120m => "120",
+        T240m => "240",
+        T360m => "360",
+        T720m => "720",
+        T1d => "1d",
+        T1w => "1w",
+        T1M => "1m",
     }
 }
 
@@ -172,15 +200,15 @@ pub struct BarhistInfo {
     pub interval_in_millis: u64,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[serde(rename_all = "kebab-case")]
-pub enum MessageType {
-    Bar,
-    DepthSnapshot,
-    Trades,
-    PlaceOrder,
-    CancelOrder,
-    CancelAll,
+string_enum! {
+    pub enum MessageType {
+        Bar => "bar",
+        DepthSnapshot => "depth-snapshot",
+        Trades => "trades",
+        PlaceOrder => "place-order",
+        CancelOrder => "cancel-order",
+        CancelAll => "cancel-all",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -204,7 +232,7 @@ pub struct BarhistData {
     #[serde(rename = "v", deserialize_with = "de_f64_str")]
     pub volume: f64,
     #[serde(rename = "ts")]
-    pub timestamp: i64,
+    pub timestamp: Timestamp,
     #[serde(rename = "i")]
     pub interval: Interval,
 }
@@ -220,7 +248,7 @@ pub struct OrderDepth {
 #[derive(Deserialize, Clone, Debug)]
 pub struct OrderDepthData {
     pub seqnum: u64,
-    pub ts: i64,
+    pub ts: Timestamp,
     pub asks: Vec<PriceQty>,
     pub bids: Vec<PriceQty>,
 }
@@ -239,7 +267,7 @@ pub struct Trade {
     pub price: Fixed9,
     #[serde(rename = "q")]
     pub qty: Fixed9,
-    pub ts: i64,
+    pub ts: Timestamp,
     #[serde(rename = "bm")]
     pub is_buyer_maker: bool,
 }
@@ -308,11 +336,11 @@ pub struct DepositBlockchainAddress {
     pub dest_tag: String,
 }
 
-#[derive(Deserialize, Serialize, Copy, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum TransactionType {
-    Deposit,
-    Withdrawal,
+string_enum! {
+    pub enum TransactionType {
+        Deposit => "deposit",
+        Withdrawal => "withdrawal",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -322,15 +350,25 @@ pub struct DestAddress {
     pub dest_tag: Option<String>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum TransactionStatus {
-    Pending,
-    Reviewing,
-    Confirmed,
-    Rejected,
-    Canceled,
-    Failed,
+string_enum! {
+    pub enum TransactionStatus {
+        Pending => "pending",
+        Reviewing => "reviewing",
+        Confirmed => "confirmed",
+        Rejected => "rejected",
+        Canceled => "canceled",
+        Failed => "failed",
+    }
+}
+
+impl TransactionStatus {
+    /// The transaction status won't change anymore, unknown statuses are assumed not final
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::Confirmed | Self::Rejected | Self::Canceled | Self::Failed
+        )
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -345,7 +383,7 @@ pub struct TransactionHistoryEntry {
     pub num_confirmed: u32,
     pub request_id: String,
     pub status: TransactionStatus,
-    pub time: i64,
+    pub time: Timestamp,
     pub transaction_type: TransactionType,
 }
 
@@ -358,28 +396,46 @@ pub struct TransactionHistory {
     pub page_size: u32,
 }
 
-#[derive(Deserialize, Serialize, Copy, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub enum OrderType {
-    #[serde(alias = "Market")]
-    Market,
-    #[serde(alias = "Limit")]
-    Limit,
+pub struct WithdrawResponse {
+    pub request_id: String,
 }
 
-#[derive(Deserialize, Serialize, Copy, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum OrderSide {
-    #[serde(alias = "Buy")]
-    Buy,
-    #[serde(alias = "Sell")]
-    Sell,
+string_enum! {
+    pub enum OrderType {
+        Market => "market" | "Market",
+        Limit => "limit" | "Limit",
+        StopMarket => "stop_market" | "StopMarket",
+        StopLimit => "stop_limit" | "StopLimit",
+    }
 }
 
-#[derive(Deserialize, Serialize, Copy, Clone, Debug)]
-pub enum TimeInForce {
-    GTC,
-    IOC,
+impl OrderType {
+    /// The order is triggered by `stop_price`
+    pub fn is_stop(&self) -> bool {
+        matches!(self, Self::StopMarket | Self::StopLimit)
+    }
+
+    /// The order needs an `order_price`
+    pub fn is_limit(&self) -> bool {
+        matches!(self, Self::Limit | Self::StopLimit)
+    }
+}
+
+string_enum! {
+    pub enum OrderSide {
+        Buy => "buy" | "Buy",
+        Sell => "sell" | "Sell",
+    }
+}
+
+string_enum! {
+    pub enum TimeInForce {
+        GTC => "GTC", // good till canceled
+        IOC => "IOC", // immediate or cancel
+        FOK => "FOK", // fill or kill
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -389,25 +445,28 @@ pub struct AckOrderInfo {
     pub order_id: String,
     pub order_type: OrderType,
     pub symbol: String,
-    pub timestamp: i64,
+    pub timestamp: Timestamp,
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub enum ExecInstruction {
-    #[serde(rename = "POST")]
-    Post,
-    Liquidation,
-    #[serde(rename = "NULL_VAL")]
-    Null,
+string_enum! {
+    pub enum ExecInstruction {
+        Post => "POST" | "Post", // post only
+        Liquidation => "Liquidation",
+        Hidden => "Hidden",
+        ReduceOnly => "ReduceOnly",
+        Null => "NULL_VAL",
+    }
 }
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum OrderStatus {
-    New,
-    PendingNew,
-    PartiallyFilled,
-    Filled,
-    Rejected,
-    Canceled,
+
+string_enum! {
+    pub enum OrderStatus {
+        New => "New",
+        PendingNew => "PendingNew",
+        PartiallyFilled => "PartiallyFilled",
+        Filled => "Filled",
+        Rejected => "Rejected",
+        Canceled => "Canceled",
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -419,7 +478,7 @@ pub struct Order {
     #[serde(deserialize_with = "empty_string_as_none")]
     pub error_code: Option<String>,
     pub fee_asset: String,
-    pub last_exec_time: i64,
+    pub last_exec_time: Timestamp,
     pub order_id: String,
     pub order_qty: Fixed9,
     pub order_type: OrderType,
@@ -459,7 +518,7 @@ pub struct AckCancelInfo {
     #[serde(deserialize_with = "empty_string_as_none")]
     pub order_type: Option<OrderType>,
     pub symbol: String,
-    pub timestamp: i64,
+    pub timestamp: Timestamp,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -478,11 +537,65 @@ pub struct CancelOrderResponse {
     pub info: CancelOrderInfo,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "status", content = "info")]
+pub enum PlaceOrderBatchInfo {
+    #[serde(rename = "Ack")]
+    Acknowledged(Vec<AckOrderInfo>),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceOrderBatchResponse {
+    pub ac: AccountType,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub info: PlaceOrderBatchInfo,
+}
+
+impl PlaceOrderBatchResponse {
+    /// Per-order results, keyed by the client order `id`
+    pub fn by_client_id(&self) -> HashMap<&str, &AckOrderInfo> {
+        match &self.info {
+            PlaceOrderBatchInfo::Acknowledged(orders) => {
+                orders.iter().map(|o| (o.id.as_str(), o)).collect()
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "status", content = "info")]
+pub enum CancelOrderBatchInfo {
+    #[serde(rename = "Ack")]
+    Acknowledged(Vec<AckCancelInfo>),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelOrderBatchResponse {
+    pub ac: AccountType,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub info: CancelOrderBatchInfo,
+}
+
+impl CancelOrderBatchResponse {
+    /// Per-order results, keyed by the client order `id`
+    pub fn by_client_id(&self) -> HashMap<&str, &AckCancelInfo> {
+        match &self.info {
+            CancelOrderBatchInfo::Acknowledged(orders) => {
+                orders.iter().map(|o| (o.id.as_str(), o)).collect()
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct AckCancelAllInfo {
     #[serde(deserialize_with = "empty_string_as_none")]
     pub symbol: Option<String>,
-    pub timestamp: i64,
+    pub timestamp: Timestamp,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -512,13 +625,13 @@ pub struct HistoryOrder {
     #[serde(deserialize_with = "empty_string_as_none")]
     pub error_code: Option<String>,
     pub fee_asset: String,
-    pub last_exec_time: i64,
+    pub last_exec_time: Timestamp,
     pub order_id: String,
     pub order_qty: Fixed9,
     pub order_type: OrderType,
     pub price: Fixed9,
     pub seq_num: u64,
-    pub sending_time: i64,
+    pub sending_time: Timestamp,
     pub side: OrderSide,
     #[serde(deserialize_with = "empty_string_as_none")]
     pub stop_price: Option<Fixed9>,
@@ -536,3 +649,56 @@ pub struct OrderHistoryPage {
     pub page: u32,
     pub page_size: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_values_round_trip() {
+        let order_type: OrderType = serde_json::from_str(r#""trailing_stop""#).unwrap();
+        assert_eq!(order_type, OrderType::Unknown("trailing_stop".into()));
+        assert_eq!(
+            serde_json::to_string(&order_type).unwrap(),
+            r#""trailing_stop""#
+        );
+
+        let interval: Interval = serde_json::from_str(r#""3d""#).unwrap();
+        assert_eq!(interval, Interval::Unknown("3d".into()));
+        assert_eq!(serde_json::to_string(&interval).unwrap(), r#""3d""#);
+
+        let tx_type: TransactionType = serde_json::from_str(r#""transfer""#).unwrap();
+        assert_eq!(serde_json::to_string(&tx_type).unwrap(), r#""transfer""#);
+    }
+
+    #[test]
+    fn stop_order_types() {
+        assert_eq!(
+            serde_json::to_string(&OrderType::StopMarket).unwrap(),
+            r#""stop_market""#
+        );
+        assert_eq!(
+            serde_json::to_string(&OrderType::StopLimit).unwrap(),
+            r#""stop_limit""#
+        );
+        for (value, expected) in [
+            (r#""StopMarket""#, OrderType::StopMarket),
+            (r#""stop_market""#, OrderType::StopMarket),
+            (r#""StopLimit""#, OrderType::StopLimit),
+            (r#""stop_limit""#, OrderType::StopLimit),
+        ] {
+            assert_eq!(serde_json::from_str::<OrderType>(value).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn known_values_accept_aliases() {
+        let side: OrderSide = serde_json::from_str(r#""Buy""#).unwrap();
+        assert_eq!(side, OrderSide::Buy);
+        assert_eq!(serde_json::to_string(&side).unwrap(), r#""buy""#);
+
+        let status: TransactionStatus = serde_json::from_str(r#""confirmed""#).unwrap();
+        assert!(status.is_terminal());
+        assert!(!TransactionStatus::Unknown("processing".into()).is_terminal());
+    }
+}
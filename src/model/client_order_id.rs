@@ -0,0 +1,83 @@
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+use crate::error::BitMaxError;
+
+const MIN_LEN: usize = 9;
+const MAX_LEN: usize = 32;
+
+/// Client generated order id, 9 to 32 characters among `[a-zA-Z0-9-_]`.
+///
+/// The exchange uses it to reject duplicates, which makes order requests safe to retry.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ClientOrderId {
+    buf: [u8; MAX_LEN],
+    len: u8,
+}
+
+impl ClientOrderId {
+    /// Random id of the maximal length
+    pub fn new() -> Self {
+        let mut buf = [0; MAX_LEN];
+        let mut rng = rand::thread_rng();
+        for byte in buf.iter_mut() {
+            *byte = rng.sample(Alphanumeric) as u8;
+        }
+
+        Self {
+            buf,
+            len: MAX_LEN as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ascii characters are accepted by `from_str`
+        std::str::from_utf8(&self.buf[..self.len as usize]).unwrap()
+    }
+}
+
+impl Default for ClientOrderId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for ClientOrderId {
+    type Err = BitMaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid_char = |c: u8| c.is_ascii_alphanumeric() || c == b'-' || c == b'_';
+        if s.len() < MIN_LEN || s.len() > MAX_LEN || !s.bytes().all(valid_char) {
+            return Err(BitMaxError::InvalidRequest(format!(
+                "invalid client order id {:?}, expected {} to {} characters among [a-zA-Z0-9-_]",
+                s, MIN_LEN, MAX_LEN
+            )));
+        }
+
+        let mut buf = [0; MAX_LEN];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Self {
+            buf,
+            len: s.len() as u8,
+        })
+    }
+}
+
+impl fmt::Display for ClientOrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for ClientOrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ClientOrderId({:?})", self.as_str())
+    }
+}
+
+impl Serialize for ClientOrderId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
@@ -1,11 +1,13 @@
-use failure::Fallible;
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
+    convert::TryFrom,
     fmt,
     ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign},
     str::FromStr,
 };
 
+use crate::error::{BitMaxError, Result};
+
 // Fixed9 represents a fixed-point number with precision 10^-9
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Default, Debug)]
 pub struct Fixed9(pub i64);
@@ -27,6 +29,47 @@ impl Fixed9 {
     pub fn decimal(self) -> i64 {
         self.0 / FIXED9_DECIMALS
     }
+
+    /// Rounds to a multiple of `step`, e.g. a tick or lot size. A non-positive step
+    /// leaves the value unchanged.
+    pub fn round_to(self, step: Fixed9, mode: RoundingMode) -> Fixed9 {
+        if step.0 <= 0 {
+            return self;
+        }
+
+        let floor = self.0.div_euclid(step.0) * step.0;
+        let remainder = self.0 - floor;
+        let rounded = match mode {
+            RoundingMode::Down => floor,
+            RoundingMode::Up if remainder > 0 => floor + step.0,
+            RoundingMode::Up => floor,
+            // compared to the distance to the next multiple, `2 * remainder` may overflow
+            RoundingMode::Nearest if remainder >= step.0 - remainder => floor + step.0,
+            RoundingMode::Nearest => floor,
+        };
+
+        Fixed9(rounded)
+    }
+
+    /// Product of two values, truncated to the Fixed9 precision.
+    /// Fails if the product is out of the Fixed9 range.
+    pub fn mul_fixed(self, rhs: Fixed9) -> Result<Fixed9> {
+        let product = self.0 as i128 * rhs.0 as i128 / FIXED9_DECIMALS as i128;
+
+        i64::try_from(product)
+            .map(Fixed9)
+            .map_err(|_| BitMaxError::Fixed9Overflow(format!("{} * {}", self, rhs)))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Towards negative infinity
+    Down,
+    /// Towards positive infinity
+    Up,
+    /// To the closest multiple, halves are rounded up
+    Nearest,
 }
 
 impl fmt::Display for Fixed9 {
@@ -126,32 +169,30 @@ impl<'de> Deserialize<'de> for Fixed9 {
 }
 
 impl FromStr for Fixed9 {
-    type Err = failure::Error;
+    type Err = BitMaxError;
 
-    fn from_str(v: &str) -> Fallible<Self> {
-        let (v, sign): (&str, i64) = if v.starts_with('-') {
-            (&v[1..], -1)
-        } else {
-            (v, 1)
+    fn from_str(v: &str) -> Result<Self> {
+        let (v, sign): (&str, i64) = match v.strip_prefix('-') {
+            Some(v) => (v, -1),
+            None => (v, 1),
         };
 
         let mut parts = v.split('.');
 
         // Unwrap is safe here bc split always produces at least one value
-        let decimals: i64 = parts
-            .next()
-            .unwrap()
-            .parse()
-            .map_err(|_| failure::format_err!("couldn't parse decimal part of fixed9 value"))?;
+        let decimals: i64 =
+            parts.next().unwrap().parse().map_err(|_| {
+                BitMaxError::ParseFixed9(format!("invalid decimal part in {:?}", v))
+            })?;
         let residual: i64 = match parts.next() {
             Some(value) => {
-                if value == "" {
+                if value.is_empty() {
                     0
                 } else {
                     let value = if value.len() > 9 { &value[..9] } else { value };
 
                     value.parse::<i64>().map_err(|_| {
-                        failure::format_err!("couldn't parse float part of fixed9 value")
+                        BitMaxError::ParseFixed9(format!("invalid float part in {:?}", v))
                     })? * MULT_TABLE[9 - value.len()]
                 }
             }
@@ -161,3 +202,72 @@ impl FromStr for Fixed9 {
         Ok(Fixed9(sign * (decimals * FIXED9_DECIMALS + residual)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn px(value: &str) -> Fixed9 {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn round_to_positive_values() {
+        let step = px("0.5");
+
+        assert_eq!(px("1.2").round_to(step, RoundingMode::Down), px("1"));
+        assert_eq!(px("1.2").round_to(step, RoundingMode::Up), px("1.5"));
+        assert_eq!(px("1.2").round_to(step, RoundingMode::Nearest), px("1"));
+        assert_eq!(px("1.5").round_to(step, RoundingMode::Up), px("1.5"));
+    }
+
+    #[test]
+    fn round_to_negative_values() {
+        let step = px("0.5");
+
+        assert_eq!(px("-1.2").round_to(step, RoundingMode::Down), px("-1.5"));
+        assert_eq!(px("-1.2").round_to(step, RoundingMode::Up), px("-1"));
+        assert_eq!(px("-1.2").round_to(step, RoundingMode::Nearest), px("-1"));
+        assert_eq!(px("-1.4").round_to(step, RoundingMode::Nearest), px("-1.5"));
+    }
+
+    #[test]
+    fn round_to_nearest_rounds_halves_up() {
+        let step = px("1");
+
+        assert_eq!(px("2.5").round_to(step, RoundingMode::Nearest), px("3"));
+        assert_eq!(px("-2.5").round_to(step, RoundingMode::Nearest), px("-2"));
+    }
+
+    #[test]
+    fn round_to_nearest_large_step() {
+        let step = Fixed9(i64::MAX - 1);
+
+        assert_eq!(
+            Fixed9(i64::MAX / 2 + 1).round_to(step, RoundingMode::Nearest),
+            step
+        );
+        assert_eq!(
+            Fixed9(i64::MAX / 2 - 1).round_to(step, RoundingMode::Nearest),
+            Fixed9(0)
+        );
+    }
+
+    #[test]
+    fn round_to_non_positive_step() {
+        assert_eq!(px("1.23").round_to(Fixed9(0), RoundingMode::Up), px("1.23"));
+        assert_eq!(
+            px("1.23").round_to(px("-0.1"), RoundingMode::Down),
+            px("1.23")
+        );
+    }
+
+    #[test]
+    fn mul_fixed_overflow() {
+        assert_eq!(px("1.5").mul_fixed(px("-2")).unwrap(), px("-3"));
+        assert!(matches!(
+            Fixed9(i64::MAX).mul_fixed(px("2")),
+            Err(BitMaxError::Fixed9Overflow(_))
+        ));
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{
+    error::{BitMaxError, Result},
+    model::{
+        websocket::{DepthData, WsOutMessage, WsRequest},
+        Fixed9, OrderDepthData, OrderSide, PriceQty, Timestamp,
+    },
+};
+
+/// Updates received while the book waits for a snapshot are kept up to this number
+const MAX_BUFFERED_UPDATES: usize = 1000;
+
+/// Level 2 order book of a symbol, built from a depth snapshot and the following depth updates.
+///
+/// Updates must have consecutive `seqnum`s. When one is missed, the book is cleared and
+/// waits for a new snapshot, e.g. from the `OrderDepth` request or the message returned by
+/// `snapshot_request`. Updates received in the meantime are buffered and applied on top of
+/// the snapshot.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    symbol: String,
+    bids: BTreeMap<Fixed9, Fixed9>,
+    asks: BTreeMap<Fixed9, Fixed9>,
+    // `None` until a snapshot is applied
+    seqnum: Option<u64>,
+    ts: Timestamp,
+    buffered: VecDeque<DepthData>,
+}
+
+impl OrderBook {
+    pub fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.into(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            seqnum: None,
+            ts: Timestamp::default(),
+            buffered: VecDeque::new(),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Sequence number of the last update applied, `None` while waiting for a snapshot
+    pub fn seqnum(&self) -> Option<u64> {
+        self.seqnum
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.seqnum.is_some()
+    }
+
+    /// Time of the last update applied
+    pub fn timestamp(&self) -> Timestamp {
+        self.ts
+    }
+
+    /// Websocket message requesting a snapshot of the book
+    pub fn snapshot_request(&self) -> WsOutMessage<'_> {
+        WsOutMessage::Request {
+            action: WsRequest::DepthSnapshot {
+                symbol: &self.symbol,
+            },
+            id: None,
+            account: None,
+        }
+    }
+
+    /// Applies a `WsInMessage::DepthSnapshot`
+    pub fn apply_snapshot(&mut self, snapshot: &DepthData) -> Result<()> {
+        self.reset(snapshot.seqnum, snapshot.ts, &snapshot.asks, &snapshot.bids)
+    }
+
+    /// Applies the result of an `OrderDepth` request
+    pub fn apply_rest_snapshot(&mut self, snapshot: &OrderDepthData) -> Result<()> {
+        self.reset(snapshot.seqnum, snapshot.ts, &snapshot.asks, &snapshot.bids)
+    }
+
+    /// Applies a `WsInMessage::Depth` update. Updates older than the book are ignored.
+    ///
+    /// Returns `BitMaxError::SequenceGap` if updates were missed, the book then needs a
+    /// new snapshot.
+    pub fn apply_update(&mut self, update: &DepthData) -> Result<()> {
+        let seqnum = match self.seqnum {
+            Some(seqnum) => seqnum,
+            None => {
+                if self.buffered.len() == MAX_BUFFERED_UPDATES {
+                    self.buffered.pop_front();
+                }
+                self.buffered.push_back(update.clone());
+                return Ok(());
+            }
+        };
+
+        if update.seqnum <= seqnum {
+            return Ok(());
+        }
+        if update.seqnum != seqnum + 1 {
+            self.seqnum = None;
+            self.bids.clear();
+            self.asks.clear();
+            self.buffered.clear();
+            self.buffered.push_back(update.clone());
+
+            return Err(BitMaxError::SequenceGap {
+                expected: seqnum + 1,
+                received: update.seqnum,
+            });
+        }
+
+        apply_levels(&mut self.asks, &update.asks);
+        apply_levels(&mut self.bids, &update.bids);
+        self.seqnum = Some(update.seqnum);
+        self.ts = update.ts;
+
+        Ok(())
+    }
+
+    fn reset(
+        &mut self,
+        seqnum: u64,
+        ts: Timestamp,
+        asks: &[PriceQty],
+        bids: &[PriceQty],
+    ) -> Result<()> {
+        self.asks.clear();
+        self.bids.clear();
+        apply_levels(&mut self.asks, asks);
+        apply_levels(&mut self.bids, bids);
+        self.seqnum = Some(seqnum);
+        self.ts = ts;
+
+        // after a gap, the following updates are buffered again for the next snapshot
+        let mut result = Ok(());
+        for update in std::mem::take(&mut self.buffered) {
+            if let Err(e) = self.apply_update(&update) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Highest bid price and quantity
+    pub fn best_bid(&self) -> Option<PriceQty> {
+        self.bids.iter().next_back().map(|(p, q)| (*p, *q))
+    }
+
+    /// Lowest ask price and quantity
+    pub fn best_ask(&self) -> Option<PriceQty> {
+        self.asks.iter().next().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn spread(&self) -> Option<Fixed9> {
+        Some(self.best_ask()?.0 - self.best_bid()?.0)
+    }
+
+    pub fn mid(&self) -> Option<Fixed9> {
+        Some(Fixed9((self.best_ask()?.0 .0 + self.best_bid()?.0 .0) / 2))
+    }
+
+    /// Quantity at `price` on the bid (`Buy`) or ask (`Sell`) side, zero for an empty level
+    pub fn depth_at(&self, side: OrderSide, price: Fixed9) -> Fixed9 {
+        let levels = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+            OrderSide::Unknown(_) => return Fixed9(0),
+        };
+
+        levels.get(&price).copied().unwrap_or_default()
+    }
+
+    /// Best `n` bid levels, best first
+    pub fn top_bids(&self, n: usize) -> Vec<PriceQty> {
+        self.bids
+            .iter()
+            .rev()
+            .take(n)
+            .map(|(p, q)| (*p, *q))
+            .collect()
+    }
+
+    /// Best `n` ask levels, best first
+    pub fn top_asks(&self, n: usize) -> Vec<PriceQty> {
+        self.asks.iter().take(n).map(|(p, q)| (*p, *q)).collect()
+    }
+}
+
+fn apply_levels(book: &mut BTreeMap<Fixed9, Fixed9>, levels: &[PriceQty]) {
+    for (price, qty) in levels {
+        if *qty == Fixed9(0) {
+            book.remove(price);
+        } else {
+            book.insert(*price, *qty);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixed9::FIXED9_DECIMALS;
+
+    fn px(value: i64) -> Fixed9 {
+        Fixed9(value * FIXED9_DECIMALS)
+    }
+
+    fn level(price: i64, qty: i64) -> PriceQty {
+        (px(price), px(qty))
+    }
+
+    fn depth(seqnum: u64, asks: Vec<PriceQty>, bids: Vec<PriceQty>) -> DepthData {
+        DepthData {
+            ts: Timestamp(seqnum as i64),
+            seqnum,
+            asks,
+            bids,
+        }
+    }
+
+    fn synced_book() -> OrderBook {
+        let mut book = OrderBook::new("BTC/USDT");
+        book.apply_snapshot(&depth(
+            10,
+            vec![level(101, 1), level(102, 2), level(103, 3)],
+            vec![level(99, 1), level(98, 2), level(97, 3)],
+        ))
+        .unwrap();
+        book
+    }
+
+    #[test]
+    fn snapshot_replays_buffered_updates() {
+        let mut book = OrderBook::new("BTC/USDT");
+        book.apply_update(&depth(9, vec![level(101, 5)], vec![]))
+            .unwrap();
+        book.apply_update(&depth(11, vec![level(101, 0)], vec![level(100, 4)]))
+            .unwrap();
+        assert!(!book.is_synced());
+
+        book.apply_snapshot(&depth(10, vec![level(101, 1)], vec![level(99, 1)]))
+            .unwrap();
+
+        assert_eq!(book.seqnum(), Some(11));
+        assert_eq!(book.best_ask(), None);
+        assert_eq!(book.best_bid(), Some(level(100, 4)));
+    }
+
+    #[test]
+    fn gap_resets_the_book() {
+        let mut book = synced_book();
+
+        let err = book
+            .apply_update(&depth(12, vec![level(101, 5)], vec![]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BitMaxError::SequenceGap {
+                expected: 11,
+                received: 12
+            }
+        ));
+        assert!(!book.is_synced());
+        assert_eq!(book.best_bid(), None);
+
+        book.apply_update(&depth(13, vec![], vec![level(99, 7)]))
+            .unwrap();
+        book.apply_snapshot(&depth(11, vec![level(101, 1)], vec![level(99, 1)]))
+            .unwrap();
+        assert_eq!(book.seqnum(), Some(13));
+        assert_eq!(book.best_ask(), Some(level(101, 5)));
+        assert_eq!(book.best_bid(), Some(level(99, 7)));
+    }
+
+    #[test]
+    fn gap_during_replay_keeps_later_updates() {
+        let mut book = OrderBook::new("BTC/USDT");
+        book.apply_update(&depth(13, vec![level(101, 5)], vec![]))
+            .unwrap();
+        book.apply_update(&depth(14, vec![], vec![level(99, 7)]))
+            .unwrap();
+
+        // the snapshot lags behind the buffered updates
+        assert!(book
+            .apply_snapshot(&depth(10, vec![level(101, 1)], vec![]))
+            .is_err());
+        assert!(!book.is_synced());
+
+        book.apply_snapshot(&depth(12, vec![level(101, 1)], vec![]))
+            .unwrap();
+        assert_eq!(book.seqnum(), Some(14));
+        assert_eq!(book.best_ask(), Some(level(101, 5)));
+        assert_eq!(book.best_bid(), Some(level(99, 7)));
+    }
+
+    #[test]
+    fn stale_updates_are_ignored() {
+        let mut book = synced_book();
+
+        book.apply_update(&depth(10, vec![level(101, 0)], vec![]))
+            .unwrap();
+        book.apply_update(&depth(8, vec![level(100, 1)], vec![]))
+            .unwrap();
+
+        assert_eq!(book.seqnum(), Some(10));
+        assert_eq!(book.best_ask(), Some(level(101, 1)));
+    }
+
+    #[test]
+    fn best_levels_and_mid() {
+        let book = synced_book();
+
+        assert_eq!(book.best_bid(), Some(level(99, 1)));
+        assert_eq!(book.best_ask(), Some(level(101, 1)));
+        assert_eq!(book.spread(), Some(px(2)));
+        assert_eq!(book.mid(), Some(px(100)));
+        assert_eq!(book.depth_at(OrderSide::Sell, px(102)), px(2));
+        assert_eq!(book.depth_at(OrderSide::Buy, px(100)), Fixed9(0));
+    }
+
+    #[test]
+    fn top_levels_are_best_first() {
+        let book = synced_book();
+
+        assert_eq!(book.top_bids(2), vec![level(99, 1), level(98, 2)]);
+        assert_eq!(book.top_asks(2), vec![level(101, 1), level(102, 2)]);
+        assert_eq!(book.top_asks(10).len(), 3);
+    }
+}
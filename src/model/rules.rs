@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use crate::{
+    client::request::PlaceOrder,
+    error::{BitMaxError, Result},
+    model::{Fixed9, Product, RoundingMode},
+};
+
+impl Product {
+    pub fn round_price(&self, price: Fixed9, mode: RoundingMode) -> Fixed9 {
+        price.round_to(self.tick_size, mode)
+    }
+
+    pub fn round_qty(&self, qty: Fixed9, mode: RoundingMode) -> Fixed9 {
+        qty.round_to(self.lot_size, mode)
+    }
+
+    /// Checks the precision of the order and, when it has a price, its notional value
+    pub fn check(&self, order: &PlaceOrder<'_>) -> Result<()> {
+        let invalid = |reason: String| Err(BitMaxError::InvalidRequest(reason));
+
+        if order.symbol != self.symbol {
+            return invalid(format!(
+                "rules of {} given to check a {} order",
+                self.symbol, order.symbol
+            ));
+        }
+        if self.round_qty(order.order_qty, RoundingMode::Down) != order.order_qty {
+            return invalid(format!(
+                "quantity {} is not a multiple of the {} lot size {}",
+                order.order_qty, self.symbol, self.lot_size
+            ));
+        }
+        for price in order.order_price.iter().chain(&order.stop_price) {
+            if self.round_price(*price, RoundingMode::Down) != *price {
+                return invalid(format!(
+                    "price {} is not a multiple of the {} tick size {}",
+                    price, self.symbol, self.tick_size
+                ));
+            }
+        }
+
+        // market orders are checked against the stop price, if any
+        if let Some(price) = order.order_price.or(order.stop_price) {
+            let notional = match price.mul_fixed(order.order_qty) {
+                Ok(notional) => notional,
+                Err(_) => {
+                    return invalid(format!(
+                        "notional of {} at {} exceeds the {} bounds",
+                        order.order_qty, price, self.symbol
+                    ))
+                }
+            };
+            if notional < self.min_notional || notional > self.max_notional {
+                return invalid(format!(
+                    "notional {} is outside of the {} bounds [{}, {}]",
+                    notional, self.symbol, self.min_notional, self.max_notional
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Trading rules of the products listed on the exchange, built from the `Products` request.
+///
+/// Use `conform` when building orders from computed prices and quantities, and `check`
+/// to reject orders that the exchange would refuse before sending them.
+#[derive(Clone, Debug, Default)]
+pub struct ProductRules {
+    products: HashMap<String, Product>,
+}
+
+impl ProductRules {
+    pub fn new(products: Vec<Product>) -> Self {
+        products.into_iter().collect()
+    }
+
+    pub fn products(&self) -> impl Iterator<Item = &Product> {
+        self.products.values()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Product> {
+        self.products.get(symbol)
+    }
+
+    pub fn product(&self, symbol: &str) -> Result<&Product> {
+        self.get(symbol)
+            .ok_or_else(|| BitMaxError::InvalidRequest(format!("unknown product {}", symbol)))
+    }
+
+    pub fn check(&self, order: &PlaceOrder<'_>) -> Result<()> {
+        self.product(order.symbol)?.check(order)
+    }
+
+    /// Rounds the prices of the order to the tick size with `price_mode` and its
+    /// quantity to the lot size with `qty_mode`, then checks the result
+    pub fn conform<'a>(
+        &self,
+        order: PlaceOrder<'a>,
+        price_mode: RoundingMode,
+        qty_mode: RoundingMode,
+    ) -> Result<PlaceOrder<'a>> {
+        let product = self.product(order.symbol)?;
+        let order = PlaceOrder {
+            order_qty: product.round_qty(order.order_qty, qty_mode),
+            order_price: order
+                .order_price
+                .map(|p| product.round_price(p, price_mode)),
+            stop_price: order.stop_price.map(|p| product.round_price(p, price_mode)),
+            ..order
+        };
+
+        product.check(&order)?;
+        Ok(order)
+    }
+}
+
+impl std::iter::FromIterator<Product> for ProductRules {
+    fn from_iter<I: IntoIterator<Item = Product>>(products: I) -> Self {
+        Self {
+            products: products
+                .into_iter()
+                .map(|p| (p.symbol.clone(), p))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{ComissionType, OrderSide};
+
+    fn px(value: &str) -> Fixed9 {
+        value.parse().unwrap()
+    }
+
+    fn product() -> Product {
+        Product {
+            symbol: "BTC/USDT".into(),
+            base_asset: "BTC".into(),
+            quote_asset: "USDT".into(),
+            min_notional: px("5"),
+            max_notional: px("100000"),
+            tick_size: px("0.01"),
+            lot_size: px("0.0001"),
+            margin_tradable: true,
+            commission_type: ComissionType::Quote,
+            commission_reserve_rate: px("0.001"),
+        }
+    }
+
+    #[test]
+    fn check_order() {
+        let product = product();
+        let order = PlaceOrder::limit("BTC/USDT", OrderSide::Buy, px("0.01"), px("1000"));
+
+        assert!(product.check(&order).is_ok());
+        assert!(product
+            .check(&PlaceOrder {
+                symbol: "ETH/USDT",
+                ..order.clone()
+            })
+            .is_err());
+        assert!(product
+            .check(&PlaceOrder {
+                order_qty: px("0.00015"),
+                ..order.clone()
+            })
+            .is_err());
+        assert!(product
+            .check(&PlaceOrder {
+                order_price: Some(px("1000.005")),
+                ..order.clone()
+            })
+            .is_err());
+        // notional of 1
+        assert!(product
+            .check(&PlaceOrder {
+                order_price: Some(px("100")),
+                ..order.clone()
+            })
+            .is_err());
+        assert!(product
+            .check(&PlaceOrder {
+                order_qty: px("9000000"),
+                ..order.clone()
+            })
+            .is_err());
+        // market orders have no notional check without a stop price
+        assert!(product
+            .check(&PlaceOrder::market(
+                "BTC/USDT",
+                OrderSide::Buy,
+                px("0.0001")
+            ))
+            .is_ok());
+    }
+}
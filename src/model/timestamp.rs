@@ -0,0 +1,89 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+/// Unix timestamp in milliseconds, the time unit used throughout the exchange API
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    /// Local time, use `BitMaxClient::server_time` for the exchange time
+    pub fn now() -> Self {
+        Utc::now().into()
+    }
+
+    pub fn from_millis(millis: i64) -> Self {
+        Timestamp(millis)
+    }
+
+    /// `None` if the timestamp doesn't fit in milliseconds
+    pub fn from_secs(secs: i64) -> Option<Self> {
+        secs.checked_mul(1000).map(Timestamp)
+    }
+
+    pub fn as_millis(self) -> i64 {
+        self.0
+    }
+
+    /// `None` if the timestamp is out of the range of `DateTime`
+    pub fn to_datetime(self) -> Option<DateTime<Utc>> {
+        Utc.timestamp_millis_opt(self.0).single()
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Timestamp(datetime.timestamp_millis())
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_datetime() {
+            Some(datetime) => write!(f, "{}", datetime.to_rfc3339()),
+            None => write!(f, "{}ms", self.0),
+        }
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 + rhs.num_milliseconds())
+    }
+}
+
+impl AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.0 += rhs.num_milliseconds()
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 - rhs.num_milliseconds())
+    }
+}
+
+impl SubAssign<Duration> for Timestamp {
+    fn sub_assign(&mut self, rhs: Duration) {
+        self.0 -= rhs.num_milliseconds()
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::milliseconds(self.0 - rhs.0)
+    }
+}
@@ -1,11 +1,11 @@
 use serde::{Deserialize, Serialize, Serializer};
 
 use crate::{
-    model::{self, de_f64_str, AccountType, Fixed9, Interval, PriceQty},
-    request,
+    model::{self, de_f64_str, AccountType, Fixed9, Interval, PriceQty, Timestamp},
+    request::{self, Request},
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum SubscribeTopic<'a> {
     Depth { symbol: &'a str },
     Bbo { symbol: &'a str },
@@ -14,38 +14,76 @@ pub enum SubscribeTopic<'a> {
     RefPx { symbol: &'a str },
 }
 
-impl<'a> Serialize for SubscribeTopic<'a> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let ch = match self {
+impl SubscribeTopic<'_> {
+    /// Channel name, as echoed in the `ch` field of `WsInMessage::Subscribed`
+    pub fn channel(&self) -> String {
+        match self {
             Self::Depth { symbol } => format!("depth:{}", symbol),
             Self::Bbo { symbol } => format!("bbo:{}", symbol),
             Self::Trades { symbol } => format!("trades:{}", symbol),
             Self::Bar { symbol, interval } => format!("bar:{}:{}", interval, symbol),
             Self::RefPx { symbol } => format!("ref-px:{}", symbol),
-        };
+        }
+    }
+}
 
-        serializer.serialize_str(&ch)
+impl<'a> Serialize for SubscribeTopic<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.channel())
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "action", content = "args", rename_all = "kebab-case")]
 pub enum WsRequest<'a> {
     PlaceOrder(request::PlaceOrder<'a>),
-    CancelOrder,
-    CancelAll,
+    BatchPlaceOrder(request::PlaceOrderBatch<'a>),
+    BatchCancelOrder(request::CancelOrderBatch<'a>),
+    CancelOrder(request::CancelOrder<'a>),
+    CancelAll(request::CancelAllOrders<'a>),
     DepthSnapshot { symbol: &'a str },
     DepthSnapshotTop100 { symbol: &'a str },
-    MarketTrades,
-    Balance,
-    OpenOrder,
-    MarginRisk,
+    MarketTrades { symbol: &'a str, level: u32 }, // number of trades, capped at 100
+    Balance(request::Balance<'a>),
+    OpenOrder(request::OpenOrders),
+    MarginRisk(request::MarginRisk),
+}
+
+impl WsRequest<'_> {
+    /// Account type of the wrapped request, `None` for market data
+    pub fn account_type(&self) -> Option<AccountType> {
+        match self {
+            Self::PlaceOrder(r) => r.account_type(),
+            Self::BatchPlaceOrder(r) => r.account_type(),
+            Self::BatchCancelOrder(r) => r.account_type(),
+            Self::CancelOrder(r) => r.account_type(),
+            Self::CancelAll(r) => r.account_type(),
+            Self::Balance(r) => r.account_type(),
+            Self::OpenOrder(r) => r.account_type(),
+            Self::MarginRisk(r) => r.account_type(),
+            Self::DepthSnapshot { .. }
+            | Self::DepthSnapshotTop100 { .. }
+            | Self::MarketTrades { .. } => None,
+        }
+    }
+
+    /// Sets the `time` of the wrapped orders. Unlike `BitMaxClient::request`, websockets send
+    /// requests as is, call it with `BitMaxClient::server_time` before sending an order.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        match self {
+            Self::PlaceOrder(r) => r.set_timestamp(timestamp),
+            Self::BatchPlaceOrder(r) => r.set_timestamp(timestamp),
+            Self::BatchCancelOrder(r) => r.set_timestamp(timestamp),
+            Self::CancelOrder(r) => r.set_timestamp(timestamp),
+            _ => {}
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "op")]
 pub enum WsOutMessage<'a> {
     #[serde(rename = "sub")]
@@ -58,6 +96,7 @@ pub enum WsOutMessage<'a> {
         ch: SubscribeTopic<'a>,
         id: Option<&'a str>,
     },
+    /// Prefer `WsOutMessage::request`, `account` must match the account type of `action`
     #[serde(rename = "req")]
     Request {
         #[serde(flatten)]
@@ -69,6 +108,17 @@ pub enum WsOutMessage<'a> {
     Pong,
 }
 
+impl<'a> WsOutMessage<'a> {
+    /// Request message, sent to the account of the wrapped request
+    pub fn request(action: WsRequest<'a>, id: Option<&'a str>) -> Self {
+        Self::Request {
+            account: action.account_type(),
+            action,
+            id,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AuthType {
@@ -79,6 +129,7 @@ pub enum AuthType {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(tag = "m", rename_all = "kebab-case")]
 pub enum WsInMessage {
+    /// Answered by `BitMaxWebsocket`, never yielded
     Ping {
         hp: u8,
     },
@@ -97,6 +148,10 @@ pub enum WsInMessage {
         type_: AuthType,
     },
     Closed,
+    /// Sent by `ReconnectingWebsocket` once the connection is restored and the subscriptions
+    /// are replayed, the messages missed in between are lost
+    #[serde(skip_deserializing)]
+    Reconnected,
     #[serde(rename = "sub")]
     Subscribed {
         id: Option<String>,
@@ -138,11 +193,37 @@ pub enum WsInMessage {
         #[serde(flatten)]
         action: OrderAction,
     },
+    MarketTrades {
+        id: Option<String>,
+        symbol: String,
+        data: Vec<Trade>,
+    },
+    Balance {
+        id: Option<String>,
+        #[serde(rename = "accountId")]
+        account_id: String,
+        ac: AccountType,
+        data: Vec<model::Balance>,
+    },
+    OpenOrder {
+        id: Option<String>,
+        #[serde(rename = "accountId")]
+        account_id: String,
+        ac: AccountType,
+        data: Vec<model::Order>,
+    },
+    MarginRisk {
+        id: Option<String>,
+        #[serde(rename = "accountId")]
+        account_id: String,
+        ac: AccountType,
+        data: model::MarginRisk,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct DepthData {
-    pub ts: i64,
+    pub ts: Timestamp,
     pub seqnum: u64,
     pub asks: Vec<PriceQty>,
     pub bids: Vec<PriceQty>,
@@ -150,7 +231,7 @@ pub struct DepthData {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct BboData {
-    pub ts: i64,
+    pub ts: Timestamp,
     pub bid: PriceQty,
     pub ask: PriceQty,
 }
@@ -161,7 +242,7 @@ pub struct Trade {
     pub price: Fixed9,
     #[serde(rename = "q")]
     pub qty: Fixed9,
-    pub ts: i64,
+    pub ts: Timestamp,
     #[serde(rename = "bm")]
     pub is_buyer_maker: bool,
     pub seqnum: u64,
@@ -170,7 +251,7 @@ pub struct Trade {
 pub struct BarData {
     #[serde(rename = "i")]
     pub interval: Interval,
-    pub ts: i64,
+    pub ts: Timestamp,
     #[serde(rename = "o")]
     pub open: Fixed9,
     #[serde(rename = "c")]
@@ -185,13 +266,24 @@ pub struct BarData {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct RefPxData {
-    qa: String,
-    p: Fixed9,
+    #[serde(rename = "qa")]
+    pub quote_asset: String,
+    #[serde(rename = "p")]
+    pub price: Fixed9,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(tag = "action")]
+#[allow(clippy::large_enum_variant)]
 pub enum OrderAction {
     #[serde(rename = "place-order")]
     Place(model::PlaceOrderResponse),
+    #[serde(rename = "batch-place-order")]
+    BatchPlace(model::PlaceOrderBatchResponse),
+    #[serde(rename = "batch-cancel-order")]
+    BatchCancel(model::CancelOrderBatchResponse),
+    #[serde(rename = "cancel-order")]
+    Cancel(model::CancelOrderResponse),
+    #[serde(rename = "cancel-all")]
+    CancelAll(model::CancelAllResponse),
 }